@@ -0,0 +1,169 @@
+//! # Alphabets
+//!
+//! The residues allowed in DNA, RNA and protein sequences, and validation of
+//! sequence lines against them as they are read.
+
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Alphabet {
+  /// `ACGT`, plus `N` for unknown bases.
+  Dna,
+  /// `ACGU`, plus `N` for unknown bases.
+  Rna,
+  /// DNA with all of the IUPAC ambiguity codes.
+  IupacDna,
+  /// The 20 standard amino acids, the IUPAC ambiguity codes `BZJX`,
+  /// selenocysteine `U`, pyrrolysine `O`, and `*` for stop.
+  Protein,
+}
+
+impl Alphabet {
+  /// The upper case residues of the alphabet. Lower case is also accepted.
+  pub fn residues(self) -> &'static [u8] {
+    match self {
+      Alphabet::Dna => b"ACGTN",
+      Alphabet::Rna => b"ACGUN",
+      Alphabet::IupacDna => b"ACGTRYSWKMBDHVN",
+      Alphabet::Protein => b"ACDEFGHIKLMNPQRSTVWYBZJXUO*",
+    }
+  }
+
+  pub fn contains(self, residue: u8) -> bool {
+    self.residues().contains(&residue.to_ascii_uppercase())
+  }
+
+  /// Offsets of the residues in `seq` that aren't in this alphabet.
+  pub fn invalid_positions(self, seq: &str) -> Vec<usize> {
+    seq.bytes()
+      .enumerate()
+      .filter(|(_, r)| !self.contains(*r))
+      .map(|(i, _)| i)
+      .collect()
+  }
+}
+
+impl FromStr for Alphabet {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "dna" => Ok(Alphabet::Dna),
+      "rna" => Ok(Alphabet::Rna),
+      "iupac" => Ok(Alphabet::IupacDna),
+      "protein" => Ok(Alphabet::Protein),
+      e => Err(format!("Unknown alphabet `{}'. Expected dna, rna, iupac or protein", e)),
+    }
+  }
+}
+
+
+/// What to do on finding a residue that isn't in the alphabet.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Policy {
+  /// Fail with an [InvalidResidue] error.
+  Strict,
+  /// Keep each invalid residue, returning it as a warning for the caller to
+  /// report.
+  Warn,
+  /// Silently replace invalid residues with this one.
+  Replace(u8),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Validator {
+  pub alphabet: Alphabet,
+  pub policy: Policy,
+}
+
+impl Validator {
+  pub fn new(alphabet: Alphabet, policy: Policy) -> Validator {
+    Validator { alphabet, policy }
+  }
+
+  /// Check one line of sequence from record `id`, at line `line` of the input.
+  ///
+  /// Whitespace is skipped. Columns in any error count from 1, and are the
+  /// byte offset within the line. Under [Policy::Warn], the invalid residues
+  /// are returned as warnings.
+  pub fn check_line(&self, id: &str, line: usize, residues: &mut [u8]) -> Result<Vec<InvalidResidue>, InvalidResidue> {
+    let mut warnings = Vec::new();
+    for (i, r) in residues.iter_mut().enumerate() {
+      if r.is_ascii_whitespace() || self.alphabet.contains(*r) { continue }
+      let invalid = InvalidResidue {
+        id: id.to_string(),
+        line,
+        column: i + 1,
+        residue: *r as char,
+        alphabet: self.alphabet,
+      };
+      match self.policy {
+        Policy::Strict => return Err(invalid),
+        Policy::Warn => warnings.push(invalid),
+        Policy::Replace(x) => *r = x,
+      }
+    }
+    Ok(warnings)
+  }
+}
+
+/// A residue that isn't in the expected alphabet.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct InvalidResidue {
+  /// Identifier of the record holding the residue.
+  pub id: String,
+  /// Line of the input, counting from 1.
+  pub line: usize,
+  /// Column within the line, counting from 1.
+  pub column: usize,
+  pub residue: char,
+  pub alphabet: Alphabet,
+}
+
+impl Display for InvalidResidue {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    write!(f, "Residue `{}' in record `{}' at line {}, column {} is not valid {:?}",
+      self.residue.escape_default(), self.id, self.line, self.column, self.alphabet)
+  }
+}
+
+impl Error for InvalidResidue {}
+
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+
+  #[test]
+  fn test_invalid_positions() {
+    assert_eq!(Alphabet::Dna.invalid_positions("acgTNu"), vec![5]);
+    assert_eq!(Alphabet::Rna.invalid_positions("acgTNu"), vec![3]);
+    assert_eq!(Alphabet::IupacDna.invalid_positions("ACGTRYKMx"), vec![8]);
+    assert_eq!(Alphabet::Protein.invalid_positions("MKV*1"), vec![4]);
+  }
+
+  #[test]
+  fn test_check_line_policies() {
+    let strict = Validator::new(Alphabet::Dna, Policy::Strict);
+    let mut line = b"ac gx\r\n".to_vec();
+    assert_eq!(strict.check_line("seq1", 7, &mut line), Err(InvalidResidue {
+      id: "seq1".to_string(),
+      line: 7,
+      column: 5,
+      residue: 'x',
+      alphabet: Alphabet::Dna,
+    }));
+
+    let warn = Validator::new(Alphabet::Dna, Policy::Warn);
+    let warnings = warn.check_line("seq1", 7, &mut line).unwrap();
+    assert_eq!(warnings.iter().map(|w| (w.column, w.residue)).collect::<Vec<_>>(), vec![(5, 'x')]);
+    assert_eq!(line, b"ac gx\r\n");
+
+    let replace = Validator::new(Alphabet::Dna, Policy::Replace(b'N'));
+    assert_eq!(replace.check_line("seq1", 7, &mut line), Ok(vec![]));
+    assert_eq!(line, b"ac gN\r\n");
+  }
+}
@@ -0,0 +1,130 @@
+//! # Sequence checksums
+//!
+//! Digests used to track sequence identity. All of them are taken over the
+//! sequence with whitespace removed and letters upper cased, so they don't
+//! depend on line wrapping or soft-masking.
+//!
+//!   * `crc64`: the SWISS-PROT / UniProt CRC64, as 16 upper case hex digits
+//!   * `md5`: lower case hex, as used by refget and the ENA
+//!   * `seguid`: base64 of the SHA-1, without padding
+//!   * `sha512t24u`: the GA4GH refget digest, the base64url of the first 24
+//!     bytes of the SHA-512
+//!
+//! See: https://www.ebi.ac.uk/ena/cram/ and
+//! http://samtools.github.io/hts-specs/refget.html
+
+use std::str::FromStr;
+
+use base64::Engine;
+use base64::engine::general_purpose::{STANDARD_NO_PAD, URL_SAFE};
+use md5::Md5;
+use sha1::Sha1;
+use sha2::{Digest, Sha512};
+
+/// The residues of `seq`, upper cased and without whitespace.
+pub fn normalise(seq: &[u8]) -> Vec<u8> {
+  seq.iter()
+    .filter(|b| !b.is_ascii_whitespace())
+    .map(u8::to_ascii_uppercase)
+    .collect()
+}
+
+const CRC64_TABLE: [u64; 256] = {
+  let mut table = [0; 256];
+  let mut i = 0;
+  while i < 256 {
+    let mut c = i as u64;
+    let mut j = 0;
+    while j < 8 {
+      c = if c & 1 == 1 { (c >> 1) ^ 0xD800_0000_0000_0000 } else { c >> 1 };
+      j += 1;
+    }
+    table[i] = c;
+    i += 1;
+  }
+  table
+};
+
+pub fn crc64(seq: &[u8]) -> u64 {
+  normalise(seq).iter()
+    .fold(0, |crc, b| (crc >> 8) ^ CRC64_TABLE[((crc ^ *b as u64) & 0xFF) as usize])
+}
+
+pub fn md5(seq: &[u8]) -> String {
+  Md5::digest(normalise(seq)).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub fn seguid(seq: &[u8]) -> String {
+  STANDARD_NO_PAD.encode(Sha1::digest(normalise(seq)))
+}
+
+pub fn sha512t24u(seq: &[u8]) -> String {
+  URL_SAFE.encode(&Sha512::digest(normalise(seq))[..24])
+}
+
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Checksum {
+  Crc64,
+  Md5,
+  Seguid,
+  Sha512t24u,
+}
+
+impl Checksum {
+  /// The digest of `seq` as text.
+  pub fn digest(self, seq: &[u8]) -> String {
+    match self {
+      Checksum::Crc64 => format!("{:016X}", crc64(seq)),
+      Checksum::Md5 => md5(seq),
+      Checksum::Seguid => seguid(seq),
+      Checksum::Sha512t24u => sha512t24u(seq),
+    }
+  }
+
+  pub fn name(self) -> &'static str {
+    match self {
+      Checksum::Crc64 => "crc64",
+      Checksum::Md5 => "md5",
+      Checksum::Seguid => "seguid",
+      Checksum::Sha512t24u => "sha512t24u",
+    }
+  }
+}
+
+impl FromStr for Checksum {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "crc64" => Ok(Checksum::Crc64),
+      "md5" => Ok(Checksum::Md5),
+      "seguid" => Ok(Checksum::Seguid),
+      "sha512t24u" | "refget" => Ok(Checksum::Sha512t24u),
+      e => Err(format!("Unknown checksum `{}'. Expected crc64, md5, seguid or sha512t24u", e)),
+    }
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+
+  #[test]
+  fn test_known_digests() {
+    let seq = b"ACGTACGTACGT";
+    assert_eq!(Checksum::Crc64.digest(seq), "C4FBB762C4A87EBD");
+    assert_eq!(Checksum::Md5.digest(seq), "31e91beccf6059ff57c696827c0c6a4b");
+    assert_eq!(Checksum::Seguid.digest(seq), "If6HIvcnRSQDVNiAoefAzySc6i4");
+    assert_eq!(Checksum::Sha512t24u.digest(b"ACGT"), "aKF498dAxcJAqme6QYQ7EZ07-fiw8Kw2");
+  }
+
+  #[test]
+  fn test_ignores_case_and_wrapping() {
+    for c in &[Checksum::Crc64, Checksum::Md5, Checksum::Seguid, Checksum::Sha512t24u] {
+      assert_eq!(c.digest(b"acgtAC\nGTac\r\ngt"), c.digest(b"ACGTACGTACGT"));
+    }
+  }
+}
@@ -0,0 +1,188 @@
+//! # Low-complexity sequence
+//!
+//! Finds low-complexity regions by scoring each window of a sequence, either
+//! with the DUST triplet score or by the Shannon entropy of its base
+//! composition, and masks them.
+//!
+//! DUST counts each of the 64 triplets in the window, and scores it as
+//! `sum(c * (c - 1) / 2) / (l - 1)` for `l` triplets. Windows scoring above
+//! the threshold are low-complexity; 20 is the usual threshold for 64bp
+//! windows.
+//!
+//! Entropy is in bits over the `ACGT` composition of the window, so lies
+//! between 0 and 2. Windows below the threshold are low-complexity.
+//!
+//! See: Morgulis et al. (2006) https://doi.org/10.1089/cmb.2006.13.1028
+
+use std::ops::Range;
+use std::str::FromStr;
+
+use crate::mask::{mask_intervals, MaskMode};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Method {
+  Dust,
+  Entropy,
+}
+
+impl FromStr for Method {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "dust" => Ok(Method::Dust),
+      "entropy" => Ok(Method::Entropy),
+      e => Err(format!("Unknown low-complexity method `{}'. Expected dust or entropy", e)),
+    }
+  }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct LowComplexity {
+  pub method: Method,
+  pub window: usize,
+  pub threshold: f64,
+}
+
+fn base_code(b: u8) -> Option<usize> {
+  match b.to_ascii_uppercase() {
+    b'A' => Some(0),
+    b'C' => Some(1),
+    b'G' => Some(2),
+    b'T' | b'U' => Some(3),
+    _ => None,
+  }
+}
+
+fn triplet_code(t: &[u8]) -> Option<usize> {
+  Some(base_code(t[0])? << 4 | base_code(t[1])? << 2 | base_code(t[2])?)
+}
+
+/// Add `window` to the intervals, merging it with the last one if they touch.
+fn add_interval(intervals: &mut Vec<Range<usize>>, window: Range<usize>) {
+  match intervals.last_mut() {
+    Some(r) if r.end >= window.start => r.end = r.end.max(window.end),
+    _ => intervals.push(window),
+  }
+}
+
+impl LowComplexity {
+  /// DUST with 64bp windows and a threshold of 20.
+  pub fn dust() -> LowComplexity {
+    LowComplexity { method: Method::Dust, window: 64, threshold: 20.0 }
+  }
+
+  /// Entropy with 64bp windows and a threshold of 1 bit.
+  pub fn entropy() -> LowComplexity {
+    LowComplexity { method: Method::Entropy, window: 64, threshold: 1.0 }
+  }
+
+  /// The low-complexity intervals of `seq`, counting from 0, merged and in
+  /// order. Sequences shorter than the window are scored as a single window.
+  pub fn intervals(&self, seq: &str) -> Vec<Range<usize>> {
+    let seq = seq.as_bytes();
+    let window = self.window.clamp(1, seq.len().max(1));
+    if seq.is_empty() { return Vec::new() }
+    match self.method {
+      Method::Dust => self.dust_intervals(seq, window),
+      Method::Entropy => self.entropy_intervals(seq, window),
+    }
+  }
+
+  fn dust_intervals(&self, seq: &[u8], window: usize) -> Vec<Range<usize>> {
+    let mut intervals = Vec::new();
+    if window < 3 { return intervals }
+    let triplets: Vec<Option<usize>> = seq.windows(3).map(triplet_code).collect();
+    let per_window = window - 2;
+
+    let mut counts = [0usize; 64];
+    let mut total = 0;
+    let mut pairs = 0;
+    for (i, t) in triplets.iter().enumerate() {
+      if let Some(t) = t {
+        pairs += counts[*t];
+        counts[*t] += 1;
+        total += 1;
+      }
+      if i >= per_window {
+        if let Some(t) = triplets[i - per_window] {
+          counts[t] -= 1;
+          pairs -= counts[t];
+          total -= 1;
+        }
+      }
+      if i + 1 >= per_window && total > 1 && pairs as f64 / (total - 1) as f64 > self.threshold {
+        let start = i + 1 - per_window;
+        add_interval(&mut intervals, start..start + window);
+      }
+    }
+    intervals
+  }
+
+  fn entropy_intervals(&self, seq: &[u8], window: usize) -> Vec<Range<usize>> {
+    let mut intervals = Vec::new();
+    let mut counts = [0usize; 4];
+    for (i, b) in seq.iter().enumerate() {
+      if let Some(c) = base_code(*b) { counts[c] += 1 }
+      if i >= window {
+        if let Some(c) = base_code(seq[i - window]) { counts[c] -= 1 }
+      }
+      if i + 1 >= window {
+        let total: usize = counts.iter().sum();
+        if total == 0 { continue }
+        let entropy: f64 = counts.iter()
+          .filter(|c| **c > 0)
+          .map(|c| { let p = *c as f64 / total as f64; -p * p.log2() })
+          .sum();
+        if entropy < self.threshold {
+          let start = i + 1 - window;
+          add_interval(&mut intervals, start..start + window);
+        }
+      }
+    }
+    intervals
+  }
+
+  /// The fraction of bases in low-complexity regions, or 0 for an empty
+  /// sequence.
+  pub fn fraction(&self, seq: &str) -> f64 {
+    if seq.is_empty() { return 0.0 }
+    let masked: usize = self.intervals(seq).iter().map(|r| r.len()).sum();
+    masked as f64 / seq.len() as f64
+  }
+
+  /// Mask the low-complexity regions of `seq`.
+  pub fn mask(&self, seq: &str, mode: MaskMode) -> String {
+    mask_intervals(seq, &self.intervals(seq), mode)
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+
+  const COMPLEX: &str = "ATGGCTAGCTTACGGATCCGTAGCTAGGCTTACGATCGGACTTAGCAT";
+
+  #[test]
+  fn test_dust() {
+    let dust = LowComplexity { method: Method::Dust, window: 16, threshold: 2.0 };
+    let seq = format!("{}{}{}", COMPLEX, "CACACACACACACACACACACACA", COMPLEX);
+    assert_eq!(dust.intervals(COMPLEX), vec![]);
+    let intervals = dust.intervals(&seq);
+    assert_eq!(intervals.len(), 1);
+    let r = &intervals[0];
+    assert!(r.start <= 48 && r.start > 40 && r.end >= 72 && r.end < 80, "{:?}", r);
+  }
+
+  #[test]
+  fn test_entropy() {
+    let entropy = LowComplexity { method: Method::Entropy, window: 8, threshold: 1.0 };
+    assert_eq!(entropy.intervals("ACGTAAAAAAAAAACGT"), vec![Range { start: 3, end: 15 }]);
+    assert_eq!(entropy.mask("ACGTAAAAAAAAAACGT", MaskMode::Soft), "ACGtaaaaaaaaaacGT");
+    assert_eq!(entropy.mask("ACGTAAAAAAAAAACGT", MaskMode::Hard), "ACGNNNNNNNNNNNNGT");
+    assert_eq!(entropy.fraction("AAAA"), 1.0);
+    assert_eq!(entropy.fraction(""), 0.0);
+  }
+}
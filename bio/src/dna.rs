@@ -1,19 +1,38 @@
 use std::collections::HashMap;
 
-pub fn complement(n: char) -> char {
-    match n {
-        'a' => 't',
-        'c' => 'g',
-        'g' => 'c',
-        't' => 'a',
-        'n' => 'n',
+/// The complement of a nucleotide, including the IUPAC ambiguity codes.
+///
+/// Returns `None` for characters that aren't nucleotides. `U` complements to
+/// `A`, so RNA can be complemented into DNA.
+pub fn complement_checked(n: char) -> Option<char> {
+    let c = match n.to_ascii_uppercase() {
         'A' => 'T',
         'C' => 'G',
         'G' => 'C',
-        'T' => 'A',
-        _ => 'N', // fixme -- should warn
-        // _ => panic!("Unexpected nucleotide character `{}'", n),
-    }
+        'T' | 'U' => 'A',
+        'R' => 'Y',
+        'Y' => 'R',
+        'S' => 'S',
+        'W' => 'W',
+        'K' => 'M',
+        'M' => 'K',
+        'B' => 'V',
+        'V' => 'B',
+        'D' => 'H',
+        'H' => 'D',
+        'N' => 'N',
+        _ => return None,
+    };
+    Some(if n.is_ascii_lowercase() { c.to_ascii_lowercase() } else { c })
+}
+
+/// The complement of a nucleotide, with anything that isn't a nucleotide
+/// mapped to `N`.
+///
+/// Use [crate::alphabet::Validator] when reading sequences to reject or report
+/// unexpected characters, or [try_reverse_complement] to find them.
+pub fn complement(n: char) -> char {
+    complement_checked(n).unwrap_or('N')
 }
 
 pub fn reverse_complement(s: &str) -> String {
@@ -23,6 +42,15 @@ pub fn reverse_complement(s: &str) -> String {
         .collect()
 }
 
+/// Reverse complement, failing with the offset and value of the first
+/// character that isn't a nucleotide.
+pub fn try_reverse_complement(s: &str) -> Result<String, (usize, char)> {
+    s.char_indices()
+        .rev()
+        .map(|(i, n)| complement_checked(n).ok_or((i, n)))
+        .collect()
+}
+
 pub fn frame(s: &str, phase: usize) -> Vec<&str> {
     let l = s.len()-phase;
     let r = l % 3 ;
@@ -36,6 +64,19 @@ pub fn translate(codons: &[&str]) -> String {
         .collect()
 }
 
+/// Translate codons of soft-masked sequence, giving `X` for any codon with a
+/// masked (lower case) base.
+pub fn translate_masked(codons: &[&str]) -> String {
+    codons
+        .iter()
+        .map(|c| if c.bytes().any(|b| b.is_ascii_lowercase()) {
+            "X"
+        } else {
+            *(TRANSLATION_TABLE.get(&c.to_ascii_lowercase()).unwrap_or(&"*"))
+        })
+        .collect()
+}
+
 lazy_static! {
 pub static ref TRANSLATION_TABLE: HashMap<String, &'static str> = {
     let mut tab = HashMap::new();
@@ -0,0 +1,127 @@
+//! # Interval trees
+//!
+//! A static interval tree for finding the values whose intervals overlap a
+//! query. Intervals count from 0 and are half-open.
+//!
+//! The intervals are kept sorted by start, and treated as an implicit balanced
+//! binary tree with each midpoint as a node. Each node records the largest end
+//! within its subtree, so that subtrees ending before the query are skipped.
+
+use std::ops::Range;
+
+#[derive(Debug, Clone)]
+pub struct IntervalTree<T> {
+  entries: Vec<(Range<u64>, T)>,
+  /// The largest end in the subtree centred on each entry.
+  max_end: Vec<u64>,
+}
+
+impl <T> IntervalTree<T> {
+  pub fn new(mut entries: Vec<(Range<u64>, T)>) -> IntervalTree<T> {
+    entries.sort_by_key(|(r, _)| (r.start, r.end));
+    let mut max_end = vec![0; entries.len()];
+    build(&entries, &mut max_end, 0, entries.len());
+    IntervalTree { entries, max_end }
+  }
+
+  pub fn len(&self) -> usize {
+    self.entries.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.entries.is_empty()
+  }
+
+  /// Every interval and value, in order of start.
+  pub fn iter(&self) -> impl Iterator<Item = &(Range<u64>, T)> {
+    self.entries.iter()
+  }
+
+  /// The entry at position `i` of [IntervalTree::iter].
+  pub fn get(&self, i: usize) -> Option<&(Range<u64>, T)> {
+    self.entries.get(i)
+  }
+
+  /// The intervals and values overlapping `query`, in order of start.
+  pub fn overlapping(&self, query: Range<u64>) -> Vec<&(Range<u64>, T)> {
+    self.overlapping_indices(query).into_iter().map(|i| &self.entries[i]).collect()
+  }
+
+  /// The positions in [IntervalTree::iter] of the intervals overlapping
+  /// `query`, in increasing order. Positions don't change once the tree is
+  /// built, so they can stand in for the values.
+  pub fn overlapping_indices(&self, query: Range<u64>) -> Vec<usize> {
+    let mut found = Vec::new();
+    self.search(&query, 0, self.entries.len(), &mut found);
+    found
+  }
+
+  fn search(&self, query: &Range<u64>, lo: usize, hi: usize, found: &mut Vec<usize>) {
+    if lo >= hi { return }
+    let mid = lo + (hi - lo) / 2;
+    if self.max_end[mid] <= query.start { return }
+    self.search(query, lo, mid, found);
+    let entry = &self.entries[mid];
+    if entry.0.start < query.end {
+      if entry.0.end > query.start {
+        found.push(mid);
+      }
+      self.search(query, mid + 1, hi, found);
+    }
+  }
+}
+
+fn build<T>(entries: &[(Range<u64>, T)], max_end: &mut [u64], lo: usize, hi: usize) -> u64 {
+  if lo >= hi { return 0 }
+  let mid = lo + (hi - lo) / 2;
+  let end = entries[mid].0.end
+    .max(build(entries, max_end, lo, mid))
+    .max(build(entries, max_end, mid + 1, hi));
+  max_end[mid] = end;
+  end
+}
+
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+
+  #[test]
+  fn test_overlapping() {
+    let tree = IntervalTree::new(vec![
+      (Range { start: 50, end: 60 }, "d"),
+      (Range { start: 0, end: 100 }, "a"),
+      (Range { start: 10, end: 20 }, "b"),
+      (Range { start: 15, end: 30 }, "c"),
+      (Range { start: 70, end: 71 }, "e"),
+    ]);
+    let found = |q: Range<u64>| tree.overlapping(q).iter().map(|(_, v)| *v).collect::<Vec<_>>();
+    assert_eq!(found(18..19), vec!["a", "b", "c"]);
+    assert_eq!(found(20..50), vec!["a", "c"]);
+    assert_eq!(found(60..70), vec!["a"]);
+    assert_eq!(found(100..200), Vec::<&str>::new());
+    assert_eq!(found(0..1000).len(), 5);
+    assert_eq!(tree.overlapping_indices(18..19), vec![0, 1, 2]);
+    assert_eq!(tree.get(2).map(|(_, v)| *v), Some("c"));
+    assert!(IntervalTree::<()>::new(vec![]).overlapping(0..10).is_empty());
+  }
+
+  #[test]
+  fn test_overlapping_matches_a_scan() {
+    let ranges: Vec<Range<u64>> = (0..200u64).map(|i| {
+      let start = (i * 37) % 500;
+      start..start + (i * 13) % 40 + 1
+    }).collect();
+    let tree = IntervalTree::new(ranges.iter().cloned().zip(0..).collect());
+    for q in (0..520u64).step_by(7).map(|s| s..s + 11) {
+      let mut found: Vec<usize> = tree.overlapping(q.clone()).iter().map(|(_, i)| *i).collect();
+      found.sort_unstable();
+      let expected: Vec<usize> = ranges.iter().enumerate()
+        .filter(|(_, r)| r.start < q.end && r.end > q.start)
+        .map(|(i, _)| i)
+        .collect();
+      assert_eq!(found, expected);
+    }
+  }
+}
@@ -2,5 +2,11 @@
 extern crate lazy_static;
 
 
+pub mod alphabet;
+pub mod checksum;
+pub mod complexity;
 pub mod dna;
+pub mod interval;
+pub mod mask;
 pub mod seq;
+pub mod stats;
@@ -0,0 +1,109 @@
+//! # Soft-masking
+//!
+//! Repeat-masked assemblies mark masked bases in lower case. These functions
+//! find the masked intervals, and convert between soft-masked, hard-masked
+//! (`N`) and unmasked sequence.
+
+use std::ops::Range;
+use std::str::FromStr;
+
+/// How to mark masked bases.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum MaskMode {
+  /// Lower case.
+  Soft,
+  /// Replace with `N`.
+  Hard,
+}
+
+impl FromStr for MaskMode {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "soft" => Ok(MaskMode::Soft),
+      "hard" => Ok(MaskMode::Hard),
+      e => Err(format!("Unknown mask mode `{}'. Expected soft or hard", e)),
+    }
+  }
+}
+
+pub fn is_soft_masked(base: u8) -> bool {
+  base.is_ascii_lowercase()
+}
+
+/// The runs of soft-masked bases, counting from 0, in order.
+pub fn soft_masked_intervals(seq: &str) -> Vec<Range<usize>> {
+  let mut runs: Vec<Range<usize>> = Vec::new();
+  for (i, _) in seq.bytes().enumerate().filter(|(_, b)| is_soft_masked(*b)) {
+    match runs.last_mut() {
+      Some(r) if r.end == i => r.end += 1,
+      _ => runs.push(i..i + 1),
+    }
+  }
+  runs
+}
+
+/// The number of soft-masked bases.
+pub fn soft_masked_count(seq: &str) -> usize {
+  seq.bytes().filter(|b| is_soft_masked(*b)).count()
+}
+
+/// The fraction of bases that are soft-masked, or 0 for an empty sequence.
+pub fn soft_masked_fraction(seq: &str) -> f64 {
+  if seq.is_empty() { 0.0 } else { soft_masked_count(seq) as f64 / seq.len() as f64 }
+}
+
+/// Replace soft-masked bases with `N`.
+pub fn hard_mask(seq: &str) -> String {
+  seq.chars().map(|c| if c.is_ascii_lowercase() { 'N' } else { c }).collect()
+}
+
+/// Upper case every base, removing any soft-masking.
+pub fn unmask(seq: &str) -> String {
+  seq.to_ascii_uppercase()
+}
+
+/// Mask the bases in each of `intervals`, counting bytes from 0. Intervals
+/// are clamped to the sequence, and a character is masked as a whole if an
+/// interval covers any of its bytes.
+pub fn mask_intervals(seq: &str, intervals: &[Range<usize>], mode: MaskMode) -> String {
+  let mut masked = vec![false; seq.len()];
+  for r in intervals {
+    for m in &mut masked[r.start.min(seq.len())..r.end.min(seq.len())] {
+      *m = true;
+    }
+  }
+  seq.char_indices()
+    .map(|(i, c)| match (masked[i..i + c.len_utf8()].contains(&true), mode) {
+      (false, _) => c,
+      (true, MaskMode::Soft) => c.to_ascii_lowercase(),
+      (true, MaskMode::Hard) => 'N',
+    })
+    .collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+
+  #[test]
+  fn test_masking() {
+    let seq = "acGTNnnACgt";
+    assert_eq!(soft_masked_intervals(seq), vec![0..2, 5..7, 9..11]);
+    assert_eq!(soft_masked_count(seq), 6);
+    assert_eq!(hard_mask(seq), "NNGTNNNACNN");
+    assert_eq!(unmask(seq), "ACGTNNNACGT");
+    assert_eq!(soft_masked_fraction(""), 0.0);
+    assert_eq!(soft_masked_fraction("aAcC"), 0.5);
+    assert_eq!(mask_intervals("ACGTACGT", &[0..2, 5..6], MaskMode::Soft), "acGTAcGT");
+    assert_eq!(mask_intervals("ACGTACGT", &[0..2, 5..6], MaskMode::Hard), "NNGTANGT");
+    assert_eq!(mask_intervals("ACGT", &[Range { start: 2, end: 10 }], MaskMode::Hard), "ACNN");
+    // U+FFFD is three bytes, and is masked whole even when split by an interval
+    assert_eq!(mask_intervals("A\u{FFFD}GT", &[Range { start: 2, end: 5 }], MaskMode::Hard), "ANNT");
+    assert_eq!(mask_intervals("A\u{FFFD}GT", &[0..1, 4..5], MaskMode::Soft), "a\u{FFFD}gT");
+    assert_eq!("mask".parse::<MaskMode>(), Err("Unknown mask mode `mask'. Expected soft or hard".to_string()));
+  }
+}
@@ -0,0 +1,277 @@
+//! BED intervals.
+//!
+//! https://genome.ucsc.edu/FAQ/FAQformat.html#format1
+//!
+//! BED counts from 0 with half-open intervals, where GFF3 counts from 1 with
+//! closed ones. Starts are held as [ZeroBased] and ends as [HalfOpenEnd] so
+//! that moving between the two is always an explicit conversion.
+
+use std::fmt::{self, Display, Formatter};
+use std::io::{self, BufRead, Write};
+use std::ops::Range;
+use std::str::FromStr;
+
+use super::error::{LineReader, ParseError, ReadError};
+use super::gff3::{Attributes, GffRecord, OneBased, Phase, Score, Strand};
+
+// Index counted from 0 rather than 1
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub struct ZeroBased(u64);
+impl ZeroBased {
+  pub fn new(at: u64) -> ZeroBased { ZeroBased(at) }
+  pub fn at(&self) -> u64 { self.0 }
+
+  /// The same position counted from 1.
+  pub fn to_one_based(self) -> OneBased { OneBased::new(self.0 + 1) }
+}
+
+impl From<OneBased> for ZeroBased {
+  fn from(at: OneBased) -> ZeroBased { ZeroBased(at.at().saturating_sub(1)) }
+}
+
+impl Display for ZeroBased {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    write!(f, "{}", self.0)
+  }
+}
+
+// End of a half-open interval counted from 0, so the position after the last base
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub struct HalfOpenEnd(u64);
+impl HalfOpenEnd {
+  pub fn new(at: u64) -> HalfOpenEnd { HalfOpenEnd(at) }
+  pub fn at(&self) -> u64 { self.0 }
+
+  /// The last base of the interval counted from 1, which has the same value.
+  pub fn to_one_based(self) -> OneBased { OneBased::new(self.0) }
+}
+
+/// The end of the interval whose last base, counted from 1, is `last`.
+impl From<OneBased> for HalfOpenEnd {
+  fn from(last: OneBased) -> HalfOpenEnd { HalfOpenEnd(last.at()) }
+}
+
+impl Display for HalfOpenEnd {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    write!(f, "{}", self.0)
+  }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct BedRecord {
+  pub chrom: String,
+  pub start: ZeroBased,
+  pub end: HalfOpenEnd,
+  pub name: Option<String>,
+  pub score: Option<Score>,
+  pub strand: Option<Strand>,
+  /// Any further columns, such as thickStart or blocks, unparsed.
+  pub extra: Vec<String>,
+}
+
+impl BedRecord {
+  pub fn new(chrom: &str, start: ZeroBased, end: HalfOpenEnd) -> BedRecord {
+    BedRecord { chrom: chrom.to_string(), start, end, name: None, score: None, strand: None, extra: Vec::new() }
+  }
+
+  /// The interval, counting from 0 and half-open.
+  pub fn range(&self) -> Range<u64> {
+    self.start.at()..self.end.at()
+  }
+
+  /// The first and last bases, counting from 1 as in GFF3.
+  pub fn one_based(&self) -> (OneBased, OneBased) {
+    (self.start.to_one_based(), self.end.to_one_based())
+  }
+
+  /// Write the record as a line of BED.
+  pub fn write<W : Write>(&self, out: &mut W) -> io::Result<()> {
+    writeln!(out, "{}", self)
+  }
+}
+
+/// A GFF3 record for the interval, with the name as its feature type and `ID`.
+/// Intervals without a name have the feature type `region`.
+impl From<&BedRecord> for GffRecord {
+  fn from(bed: &BedRecord) -> GffRecord {
+    let (start, end) = bed.one_based();
+    let mut attributes = Attributes::default();
+    if let Some(n) = &bed.name {
+      attributes.set("ID", n);
+    }
+    GffRecord {
+      seq_id: bed.chrom.clone(),
+      source: ".".to_string(),
+      feature_type: bed.name.clone().unwrap_or_else(|| "region".to_string()),
+      start,
+      end,
+      score: bed.score.unwrap_or(Score::new(None)),
+      strand: bed.strand.unwrap_or(Strand::NoStrand),
+      phase: Phase::new(None),
+      attributes,
+    }
+  }
+}
+
+/// The interval of a GFF3 record, named by its `ID` if it has one.
+impl From<&GffRecord> for BedRecord {
+  fn from(gff: &GffRecord) -> BedRecord {
+    BedRecord {
+      chrom: gff.seq_id.clone(),
+      start: ZeroBased::from(gff.start),
+      end: HalfOpenEnd::from(gff.end),
+      name: Some(gff.attributes.get("ID").unwrap_or(".").to_string()),
+      score: Some(gff.score),
+      strand: Some(gff.strand),
+      extra: Vec::new(),
+    }
+  }
+}
+
+impl FromStr for BedRecord {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let mut columns = s.trim_end_matches(&['\r', '\n'][..]).split('\t');
+    let mut next = |name: &str| columns.next()
+      .filter(|c| !c.is_empty())
+      .ok_or_else(|| format!("No {} column", name));
+
+    let chrom = next("chrom")?.to_string();
+    let start = next("chromStart")?.parse::<u64>()
+      .map_err(|e| format!("Cannot parse chromStart as a position because {}", e))?;
+    let end = next("chromEnd")?.parse::<u64>()
+      .map_err(|e| format!("Cannot parse chromEnd as a position because {}", e))?;
+    if end < start {
+      return Err(format!("chromEnd {} is before chromStart {}", end, start))
+    }
+    let mut rec = BedRecord::new(&chrom, ZeroBased(start), HalfOpenEnd(end));
+    rec.name = columns.next().map(ToString::to_string);
+    rec.score = columns.next()
+      .map(|sc| Score::from_str(sc).map_err(|e| e.to_string()))
+      .transpose()?;
+    rec.strand = columns.next()
+      .map(|st| Strand::from_str(st).map_err(|e| e.to_string()))
+      .transpose()?;
+    rec.extra = columns.map(ToString::to_string).collect();
+    Ok(rec)
+  }
+}
+
+impl Display for BedRecord {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    write!(f, "{}\t{}\t{}", self.chrom, self.start, self.end)?;
+    // optional columns are written up to the last one that is set
+    let columns = if !self.extra.is_empty() || self.strand.is_some() { 3 }
+      else if self.score.is_some() { 2 }
+      else if self.name.is_some() { 1 }
+      else { 0 };
+    if columns >= 1 { write!(f, "\t{}", self.name.as_deref().unwrap_or("."))? }
+    if columns >= 2 {
+      match self.score {
+        Some(sc) => write!(f, "\t{}", sc)?,
+        None => write!(f, "\t0")?,
+      }
+    }
+    if columns >= 3 {
+      match self.strand {
+        Some(st) => write!(f, "\t{}", st)?,
+        None => write!(f, "\t.")?,
+      }
+    }
+    for e in &self.extra {
+      write!(f, "\t{}", e)?;
+    }
+    Ok(())
+  }
+}
+
+
+/// Errors raised while streaming BED records with a [BedReader]. A line that
+/// isn't a BED record is a [ReadError::Parse], and reading can carry on with
+/// the next line.
+pub type BedError = ReadError;
+
+/// Streaming BED reader, yielding one record per line.
+///
+/// Blank lines, comments and `track` and `browser` lines are skipped.
+pub struct BedReader<R> {
+  lines: LineReader<R>,
+  record: usize,
+  done: bool,
+}
+
+impl <R : BufRead> BedReader<R> {
+  pub fn new(reader: R) -> BedReader<R> {
+    BedReader { lines: LineReader::new(reader), record: 0, done: false }
+  }
+}
+
+impl <R : BufRead> Iterator for BedReader<R> {
+  type Item = Result<BedRecord, BedError>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    while !self.done {
+      match self.lines.read_line() {
+        Ok(false) => self.done = true,
+        Ok(true) => {
+          let line = String::from_utf8_lossy(self.lines.line());
+          let header = line.starts_with('#') || line.starts_with("track") || line.starts_with("browser");
+          if !header && !line.trim().is_empty() {
+            self.record += 1;
+            return Some(BedRecord::from_str(&line).map_err(|e| BedError::Parse(
+              ParseError::new(self.lines.line_no(), e).with_record(self.record))))
+          }
+        }
+        Err(e) => {
+          self.done = true;
+          return Some(Err(BedError::Io(e)))
+        }
+      }
+    }
+    None
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+
+  #[test]
+  fn test_coordinates() {
+    let bed = BedRecord::from_str("chr1\t0\t10").unwrap();
+    assert_eq!(bed.range(), 0..10);
+    assert_eq!(bed.one_based(), (OneBased::new(1), OneBased::new(10)));
+    assert_eq!(ZeroBased::from(OneBased::new(1)), ZeroBased::new(0));
+    assert_eq!(ZeroBased::new(9).to_one_based(), OneBased::new(10));
+    assert_eq!(HalfOpenEnd::from(OneBased::new(10)), HalfOpenEnd::new(10));
+    assert_eq!(HalfOpenEnd::new(10).to_one_based(), OneBased::new(10));
+
+    let gff = GffRecord::from(&bed);
+    assert_eq!(gff.to_string(), "chr1\t.\tregion\t1\t10\t.\t.\t.\t.");
+    assert_eq!(BedRecord::from(&gff).range(), bed.range());
+  }
+
+  #[test]
+  fn test_round_trip() {
+    for line in &["chr1\t5\t10", "chr1\t5\t10\tvector", "chr1\t5\t10\tvector\t0\t-", "chr1\t5\t10\tx\t900\t+\t5\t10\t255,0,0"] {
+      assert_eq!(&BedRecord::from_str(line).unwrap().to_string(), line);
+    }
+    assert!(BedRecord::from_str("chr1\t10\t5").is_err());
+    assert!(BedRecord::from_str("chr1\tfive\t10").is_err());
+  }
+
+  #[test]
+  fn test_reader() {
+    let input: &[u8] = b"track name=contaminants\n# comment\nctg1\t0\t25\tadapter\n\nctg1\t40\n";
+    let recs: Vec<_> = BedReader::new(input).collect();
+    assert_eq!(recs.len(), 2);
+    assert_eq!(recs[0].as_ref().unwrap().name.as_deref(), Some("adapter"));
+    match &recs[1] {
+      Err(BedError::Parse(e)) => assert_eq!((e.line, e.record), (5, Some(2))),
+      r => panic!("Expected a parse error but got {:?}", r),
+    }
+  }
+}
@@ -1,15 +1,655 @@
-use super::feature_table::FeatureTable;
+//! # EMBL flat files
+//!
+//! Entries in the EMBL and ENA flat-file format. Each line starts with a two
+//! letter line code in the first five columns, and an entry ends with a `//`
+//! line. Annotation lines are kept as text, grouped by line code, while the
+//! feature table is parsed and the sequence is collected from the lines after
+//! `SQ`.
+//!
+//! Entries are written back out in the same layout, with `XX` lines between
+//! blocks, lines wrapped at 80 columns, and the `SQ` block worked out from the
+//! sequence.
 
+use std::fmt::{self, Display, Formatter};
+use std::io::{self, BufRead, Write};
+use std::str::FromStr;
+
+use chunks::{Chunker, Delim};
+
+use super::error::{ParseError, ReadError};
+use super::feature_table::{self, FeatureTable, LINE_WIDTH};
+
+/// Columns taken by the line code and the spaces after it.
+const CODE_COLUMNS: usize = 5;
+
+/// Bases written on each sequence line, in blocks of `SEQ_BLOCK`.
+const SEQ_LINE: usize = 60;
+const SEQ_BLOCK: usize = 10;
 
 // A record of an embl-like entry.
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub struct Embl {
   pub annotations: Vec<Annotation>,
   pub features: FeatureTable,
   pub sequence: String
 }
 
+/// Consecutive lines sharing a line code, such as the `DE` lines of an entry,
+/// with the code and the spaces after it removed. Each group of `RN` to `RL`
+/// lines and each block between `XX` lines is an annotation of its own.
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub struct Annotation {
   pub name: String,
   pub values: Vec<String>,
 }
 
+impl Embl {
+  /// Parse one entry, numbering the lines of `text` from `first_line`.
+  pub fn parse(text: &str, first_line: usize) -> Result<Embl, ParseError> {
+    let mut annotations: Vec<Annotation> = Vec::new();
+    let mut feature_lines = Vec::new();
+    let mut sequence = String::new();
+    let mut grouping = false;
+    let mut seen_id = false;
+    let mut in_sequence = false;
+    let mut terminated = false;
+    let mut line_no = first_line;
+
+    for (i, line) in text.lines().enumerate() {
+      line_no = first_line + i;
+      if line.trim().is_empty() { continue }
+      if terminated {
+        return Err(ParseError::new(line_no, "text after the `//' that ends the entry"))
+      }
+      if line.starts_with("//") {
+        terminated = true;
+        continue
+      }
+
+      if in_sequence {
+        for (c, b) in line.char_indices() {
+          if b.is_ascii_alphabetic() {
+            sequence.push(b);
+          } else if !b.is_ascii_digit() && !b.is_whitespace() {
+            return Err(ParseError::new(line_no, format!("unexpected `{}' in sequence data", b.escape_default()))
+              .with_column(c + 1))
+          }
+        }
+        continue
+      }
+
+      let code = line.get(..2).filter(|c| c.bytes().all(|b| b.is_ascii_uppercase() || b.is_ascii_digit()));
+      let code = match code {
+        Some(c) if line.get(2..CODE_COLUMNS.min(line.len())).is_some_and(|s| s.trim().is_empty()) => c,
+        _ => return Err(ParseError::new(line_no, "expected a two letter line code followed by spaces")),
+      };
+      if !seen_id && code != "ID" {
+        return Err(ParseError::new(line_no, "expected the entry to start with an ID line"))
+      }
+      seen_id = true;
+      let value = line.get(CODE_COLUMNS..).unwrap_or("");
+
+      match code {
+        "XX" | "FH" => grouping = false,
+        "FT" => {
+          feature_lines.push((line_no, value));
+          grouping = false;
+        }
+        "SQ" => in_sequence = true,
+        _ => {
+          let value = value.trim_end().to_string();
+          match annotations.last_mut() {
+            Some(a) if grouping && a.name == code => a.values.push(value),
+            _ => annotations.push(Annotation { name: code.to_string(), values: vec![value] }),
+          }
+          grouping = true;
+        }
+      }
+    }
+
+    if !seen_id {
+      return Err(ParseError::new(first_line, "expected the entry to start with an ID line"))
+    }
+    if !terminated {
+      return Err(ParseError::new(line_no, "entry is missing the `//' line that ends it"))
+    }
+    let features = FeatureTable::parse_lines(feature_lines)?;
+    Ok(Embl { annotations, features, sequence })
+  }
+
+  /// The first annotation with line code `name`.
+  pub fn annotation(&self, name: &str) -> Option<&Annotation> {
+    self.annotations.iter().find(|a| a.name == name)
+  }
+
+  /// The name from the ID line: the accession in ENA entries, or the entry
+  /// name in older ones.
+  pub fn id(&self) -> Option<&str> {
+    first_field(self.annotation("ID")?)
+  }
+
+  /// The sequence version from the `SV` field of the ID line, if it has one.
+  pub fn version(&self) -> Option<&str> {
+    self.annotation("ID")?.values.first()?
+      .split(';')
+      .find_map(|f| f.trim().strip_prefix("SV "))
+      .map(str::trim)
+  }
+
+  /// The primary accession from the first AC line.
+  pub fn accession(&self) -> Option<&str> {
+    first_field(self.annotation("AC")?)
+  }
+
+  /// The DE lines joined with spaces.
+  pub fn description(&self) -> Option<String> {
+    self.annotation("DE").map(|a| a.values.join(" "))
+  }
+
+  /// Write the entry in EMBL flat-file layout, ending with its `//` line.
+  pub fn write<W : Write>(&self, out: &mut W) -> io::Result<()> {
+    write!(out, "{}", self)
+  }
+}
+
+/// The line codes that are written as one block, without `XX` lines between
+/// them. Each reference starts a new block at its `RN` line.
+fn block(code: &str) -> &str {
+  match code {
+    "OS" | "OC" | "OG" => "OS",
+    "RN" | "RC" | "RP" | "RX" | "RG" | "RA" | "RT" | "RL" => "RN",
+    c => c,
+  }
+}
+
+/// Write an `XX` line if `code` starts a new block after the `previous` one.
+fn separate(f: &mut Formatter<'_>, previous: &mut Option<String>, code: &str) -> fmt::Result {
+  if let Some(p) = previous {
+    if block(p) != block(code) || code == "RN" {
+      writeln!(f, "XX")?;
+    }
+  }
+  *previous = Some(code.to_string());
+  Ok(())
+}
+
+/// The ID line with its closing `N BP.` field set to `length`.
+fn with_length(id: &str, length: usize) -> String {
+  match id.strip_suffix(" BP.").and_then(|s| s.rfind(|c: char| !c.is_ascii_digit()).map(|i| &s[..=i])) {
+    Some(head) => format!("{}{} BP.", head, length),
+    None => id.to_string(),
+  }
+}
+
+fn write_annotation(f: &mut Formatter<'_>, a: &Annotation) -> fmt::Result {
+  for v in &a.values {
+    for line in feature_table::wrap(v, LINE_WIDTH - CODE_COLUMNS, &[' ']) {
+      writeln!(f, "{:<width$}{}", a.name, line, width = CODE_COLUMNS)?;
+    }
+  }
+  Ok(())
+}
+
+fn write_sequence(f: &mut Formatter<'_>, sequence: &str) -> fmt::Result {
+  let count = |b: u8| sequence.bytes().filter(|s| s.eq_ignore_ascii_case(&b)).count();
+  let (a, c, g, t) = (count(b'a'), count(b'c'), count(b'g'), count(b't'));
+  writeln!(f, "SQ   Sequence {} BP; {} A; {} C; {} G; {} T; {} other;",
+    sequence.len(), a, c, g, t, sequence.len() - a - c - g - t)?;
+
+  let bases = sequence.as_bytes();
+  for (i, line) in bases.chunks(SEQ_LINE).enumerate() {
+    let blocks = line.chunks(SEQ_BLOCK)
+      .map(String::from_utf8_lossy)
+      .collect::<Vec<_>>()
+      .join(" ");
+    let blocks_width = SEQ_LINE + SEQ_LINE / SEQ_BLOCK - 1;
+    writeln!(f, "{:width$}{:<blocks_width$}{:>number_width$}", "", blocks, i * SEQ_LINE + line.len(),
+      width = CODE_COLUMNS,
+      blocks_width = blocks_width,
+      number_width = LINE_WIDTH - CODE_COLUMNS - blocks_width)?;
+  }
+  Ok(())
+}
+
+/// Writes the annotations in order, then the feature table, any `CO` lines
+/// and the sequence. `FH` lines, `XX` lines and the `SQ` line are worked out
+/// afresh rather than kept from the input, and the length on the ID line is
+/// updated to match the sequence.
+impl Display for Embl {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    let mut previous = None;
+    for a in self.annotations.iter().filter(|a| a.name != "CO") {
+      separate(f, &mut previous, &a.name)?;
+      if a.name == "ID" && !self.sequence.is_empty() {
+        let values = a.values.iter().map(|v| with_length(v, self.sequence.len())).collect();
+        write_annotation(f, &Annotation { name: a.name.clone(), values })?;
+      } else {
+        write_annotation(f, a)?;
+      }
+    }
+    if !self.features.features.is_empty() {
+      separate(f, &mut previous, "FT")?;
+      writeln!(f, "FH   Key             Location/Qualifiers")?;
+      writeln!(f, "FH")?;
+      self.features.write_lines("FT   ", f)?;
+    }
+    for a in self.annotations.iter().filter(|a| a.name == "CO") {
+      separate(f, &mut previous, &a.name)?;
+      write_annotation(f, a)?;
+    }
+    if !self.sequence.is_empty() {
+      separate(f, &mut previous, "SQ")?;
+      write_sequence(f, &self.sequence)?;
+    }
+    writeln!(f, "//")
+  }
+}
+
+fn first_field(a: &Annotation) -> Option<&str> {
+  a.values.first()?.split(';').next()?.split_whitespace().next()
+}
+
+impl FromStr for Embl {
+  type Err = ParseError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    Embl::parse(s, 1)
+  }
+}
+
+
+/// Errors raised while streaming EMBL entries with an [EmblReader]. A
+/// malformed entry is a [ReadError::Parse], and reading can carry on with the
+/// next entry.
+pub type EmblError = ReadError;
+
+lazy_static! {
+  static ref ENTRY_END: Delim<'static> = Delim::new(b"\n//", true);
+}
+
+/// Streaming EMBL reader, yielding one entry at a time.
+///
+/// Entries are split at each `//` line, so one that can't be parsed is
+/// reported without stopping the entries after it. Blank text between entries
+/// is skipped.
+pub struct EmblReader<R> {
+  chunks: Chunker<'static, R>,
+  /// The line number of the start of the next chunk.
+  line_no: usize,
+  record: usize,
+}
+
+impl <R : BufRead> EmblReader<R> {
+  pub fn new(reader: R) -> EmblReader<R> {
+    EmblReader { chunks: chunks::chunks(reader, &ENTRY_END), line_no: 1, record: 0 }
+  }
+}
+
+impl <R : BufRead> Iterator for EmblReader<R> {
+  type Item = Result<Embl, EmblError>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    for chunk in &mut self.chunks {
+      let chunk = match chunk {
+        Ok(c) => c,
+        Err(e) => return Some(Err(EmblError::Io(e))),
+      };
+      let first_line = self.line_no;
+      self.line_no += chunk.iter().filter(|b| **b == b'\n').count();
+      let text = String::from_utf8_lossy(&chunk);
+      if text.trim().is_empty() { continue }
+
+      self.record += 1;
+      return Some(Embl::parse(&text, first_line)
+        .map_err(|e| EmblError::Parse(e.with_record(self.record))))
+    }
+    None
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+  use crate::seq::feature_table::{FeatureRecord, FtString, Local, Loc, LocOp, Qualifier, QualifierValue};
+
+  /// Two entries laid out as ENA writes them. The first follows the X56734
+  /// example from the EMBL user manual, with a shortened sequence.
+  const ENTRIES: &str = r#"ID   X56734; SV 1; linear; mRNA; STD; PLN; 150 BP.
+XX
+AC   X56734; S46826;
+XX
+DT   12-SEP-1991 (Rel. 29, Created)
+DT   25-NOV-2005 (Rel. 85, Last updated, Version 11)
+XX
+DE   Trifolium repens mRNA for non-cyanogenic beta-glucosidase, partial
+DE   sequence
+XX
+KW   beta-glucosidase.
+XX
+OS   Trifolium repens (white clover)
+OC   Eukaryota; Viridiplantae; Streptophyta; Embryophyta; Tracheophyta;
+OC   Spermatophyta; Magnoliopsida; eudicotyledons; Gunneridae; Pentapetalae;
+OC   rosids; fabids; Fabales; Fabaceae; Papilionoideae; Trifolieae; Trifolium.
+XX
+RN   [1]
+RP   1-150
+RA   Oxtoby E., Dunn M.A., Pancoro A., Hughes M.A.;
+RT   "Nucleotide and derived amino acid sequence of the cyanogenic
+RT   beta-glucosidase (linamarase) from white clover (Trifolium repens L.)";
+RL   Plant Mol. Biol. 17(2):209-219(1991).
+XX
+FH   Key             Location/Qualifiers
+FH
+FT   source          1..150
+FT                   /organism="Trifolium repens"
+FT                   /mol_type="mRNA"
+FT                   /clone_lib="lambda gt10"
+FT                   /db_xref="taxon:3899"
+FT   CDS             14..>150
+FT                   /product="beta-glucosidase"
+FT                   /EC_number="3.2.1.21"
+FT                   /note="non-cyanogenic; a note that carries on over a
+FT                   second line with ""quoted"" text"
+FT                   /codon_start=1
+FT                   /transl_table=1
+FT                   /protein_id="CAA40058.1"
+FT                   /translation="MDFIVAIFALFVISSFTITSTNAVEASTLLDIGNLSRSSFPRGF
+FT                   IFGAGSSAYQ"
+FT   misc_feature    complement(join(20..30,
+FT                   40..50))
+FT                   /pseudo
+XX
+SQ   Sequence 150 BP; 41 A; 28 C; 31 G; 50 T; 0 other;
+     aaacaaacca aatatggatt ttattgtagc catatttgct ctgtttgtta ttagctcatt        60
+     ggtagtagcc ctgcggccac gaacacggtg atccacagta acaacggtat cttattgtct       120
+     agcctaatgt cggttagtgt ttatgagaat                                        150
+//
+ID   AB000001; SV 2; circular; genomic DNA; STD; PRO; 70 BP.
+XX
+AC   AB000001;
+XX
+DE   Test plasmid.
+XX
+FH   Key             Location/Qualifiers
+FH
+FT   tRNA            complement(5..60)
+FT                   /anticodon=(pos:complement(30..32),aa:Phe,seq:aaa)
+FT                   /rpt_type=DISPERSED
+XX
+SQ   Sequence 70 BP; 15 A; 22 C; 17 G; 16 T; 0 other;
+     tacatgtcag cgcgcagcgt cgagtcgttc tcgatctaat agcccggctc ccataagcta        60
+     ccgtaacggt                                                               70
+//
+"#;
+
+  /// The second entry, written as it would be.
+  const WRITTEN: &str = r#"ID   AB000001; SV 2; circular; genomic DNA; STD; PRO; 70 BP.
+XX
+AC   AB000001;
+XX
+DE   Test plasmid.
+XX
+FH   Key             Location/Qualifiers
+FH
+FT   tRNA            complement(5..60)
+FT                   /anticodon=(pos:complement(30..32),aa:Phe,seq:aaa)
+FT                   /rpt_type=DISPERSED
+XX
+SQ   Sequence 70 BP; 15 A; 22 C; 17 G; 16 T; 0 other;
+     tacatgtcag cgcgcagcgt cgagtcgttc tcgatctaat agcccggctc ccataagcta        60
+     ccgtaacggt                                                               70
+//
+"#;
+
+  fn value(e: &Embl, feature: usize, name: &str) -> Option<QualifierValue> {
+    e.features.features[feature].qualifier(name).cloned()
+  }
+
+  #[test]
+  fn test_parse_entry() {
+    let e = Embl::from_str(ENTRIES.split_inclusive("//\n").next().unwrap()).unwrap();
+    assert_eq!(e.id(), Some("X56734"));
+    assert_eq!(e.accession(), Some("X56734"));
+    assert_eq!(e.version(), Some("1"));
+    assert_eq!(e.description().as_deref(), Some("Trifolium repens mRNA for non-cyanogenic beta-glucosidase, partial sequence"));
+    assert_eq!(e.annotation("DT").unwrap().values.len(), 2);
+    assert_eq!(e.annotation("OC").unwrap().values[2], "rosids; fabids; Fabales; Fabaceae; Papilionoideae; Trifolieae; Trifolium.");
+    assert_eq!(e.annotations.iter().map(|a| a.name.as_str()).collect::<Vec<_>>(),
+      vec!["ID", "AC", "DT", "DE", "KW", "OS", "OC", "RN", "RP", "RA", "RT", "RL"]);
+
+    assert_eq!(e.sequence.len(), 150);
+    assert!(e.sequence.starts_with("aaacaaaccaaatatggatt"));
+    assert!(e.sequence.ends_with("ttatgagaat"));
+
+    let keys: Vec<&str> = e.features.features.iter().map(|f| f.key.as_str()).collect();
+    assert_eq!(keys, vec!["source", "CDS", "misc_feature"]);
+    assert_eq!(e.features.features[0].qualifiers.len(), 4);
+    assert_eq!(value(&e, 0, "organism"), Some(QualifierValue::QuotedText("Trifolium repens".to_string())));
+    assert_eq!(value(&e, 1, "note").unwrap().text(),
+      "non-cyanogenic; a note that carries on over a second line with \"quoted\" text");
+    assert_eq!(value(&e, 1, "translation").unwrap().text(), "MDFIVAIFALFVISSFTITSTNAVEASTLLDIGNLSRSSFPRGFIFGAGSSAYQ");
+    assert_eq!(value(&e, 1, "transl_table"), Some(QualifierValue::Unquoted("1".to_string())));
+    assert_eq!(e.features.features[2].qualifiers.len(), 1);
+    assert_eq!(value(&e, 2, "pseudo"), None);
+  }
+
+  #[test]
+  fn test_reader() {
+    let entries = EmblReader::new(ENTRIES.as_bytes()).collect::<Result<Vec<_>, _>>().unwrap();
+    assert_eq!(entries.len(), 2);
+    let e = &entries[1];
+    assert_eq!(e.id(), Some("AB000001"));
+    assert_eq!(e.sequence, "tacatgtcagcgcgcagcgtcgagtcgttctcgatctaatagcccggctcccataagctaccgtaacggt");
+    assert_eq!(value(e, 0, "anticodon").unwrap().text(), "(pos:complement(30..32),aa:Phe,seq:aaa)");
+    assert!(matches!(value(e, 0, "rpt_type"), Some(QualifierValue::VocabularyTerm(_))));
+
+    let crlf = ENTRIES.replace('\n', "\r\n");
+    let crlf_entries = EmblReader::new(crlf.trim_end().as_bytes()).collect::<Result<Vec<_>, _>>().unwrap();
+    assert_eq!(crlf_entries, entries);
+  }
+
+  #[test]
+  fn test_reader_reports_bad_entries() {
+    let bad = ENTRIES.replacen("FT   misc_feature    complement(join(20..30,", "FT   misc_feature    complement(join(20..30,,", 1);
+    let results: Vec<_> = EmblReader::new(format!("\n{}\n\n", bad).as_bytes()).collect();
+    assert_eq!(results.len(), 2);
+    match &results[0] {
+      Err(EmblError::Parse(e)) => {
+        assert_eq!(e.line, 43);
+        assert_eq!(e.record, Some(1));
+      }
+      r => panic!("expected a parse error, not {:?}", r),
+    }
+    assert!(results[1].is_ok());
+
+    let e = |s: &str| Embl::from_str(s).unwrap_err();
+    assert_eq!(e(ENTRIES).line, 51);
+    assert_eq!(e("AC   X1;\n//\n").message, "expected the entry to start with an ID line");
+    assert_eq!(e("ID   X1;\nSQ   Sequence 2 BP;\n     ac\n").line, 3);
+    assert_eq!(e("ID   X1;\nSQ   Sequence 2 BP;\n     a-c    3\n//\n").column, Some(7));
+    assert_eq!(e("ID   X1;\nDEscription\n//\n").line, 2);
+  }
+
+  #[test]
+  fn test_write() {
+    let entries = EmblReader::new(ENTRIES.as_bytes()).collect::<Result<Vec<_>, _>>().unwrap();
+    assert_eq!(entries[1].to_string(), WRITTEN);
+
+    let written = entries[0].to_string();
+    assert!(written.lines().all(|l| l.len() <= LINE_WIDTH));
+    assert!(written.contains("\nOS   Trifolium repens (white clover)\nOC   "));
+    assert!(written.contains("\nXX\nRN   [1]\nRP   1-150\n"));
+    assert!(written.contains("\nFT   misc_feature    complement(join(20..30,40..50))\n"));
+    assert!(written.contains("\nFT                   /note=\"non-cyanogenic; a note that carries on over a second\nFT                   line with \"\"quoted\"\" text\"\n"));
+    assert!(written.ends_with("     agcctaatgt cggttagtgt ttatgagaat                                        150\n//\n"));
+    assert_eq!(Embl::from_str(&written).unwrap(), entries[0]);
+  }
+
+  #[test]
+  fn test_write_edited() {
+    let mut e = Embl::from_str(WRITTEN).unwrap();
+    e.annotations.push(Annotation { name: "CC".to_string(), values: vec![
+      "A comment added after reading the entry, long enough that it has to be wrapped onto two lines.".to_string()] });
+    e.features.features[0].qualifiers.push(Qualifier {
+      name: FtString::from_str("note").unwrap(),
+      value: Some(QualifierValue::QuotedText("added".to_string())) });
+    e.sequence.push_str("NNNN");
+
+    let written = e.to_string();
+    assert!(written.starts_with("ID   AB000001; SV 2; circular; genomic DNA; STD; PRO; 74 BP.\nXX\n"));
+    assert!(written.contains("\nXX\nCC   A comment added after reading the entry, long enough that it has to be\nCC   wrapped onto two lines.\nXX\nFH"));
+    assert!(written.contains("\nFT                   /note=\"added\"\nXX\n"));
+    assert!(written.contains("SQ   Sequence 74 BP; 15 A; 22 C; 17 G; 16 T; 4 other;\n"));
+    assert!(written.contains("     ccgtaacggt NNNN                                                          74\n"));
+
+    let mut out = Vec::new();
+    e.write(&mut out).unwrap();
+    assert_eq!(Embl::from_str(std::str::from_utf8(&out).unwrap()).unwrap().to_string(), written);
+  }
+
+  /// An entry built in code and edited, written as the EMBL user manual lays
+  /// entries out.
+  const BUILT: &str = r#"ID   AB000002; SV 1; linear; mRNA; STD; PLN; 190 BP.
+XX
+AC   AB000002;
+XX
+DE   Made-up mRNA, written from an entry built in code rather than read from a
+DE   flat file
+XX
+FH   Key             Location/Qualifiers
+FH
+FT   source          1..190
+FT                   /organism="Trifolium repens"
+FT                   /mol_type="mRNA"
+FT   CDS             join(1..30,41..>190)
+FT                   /note="edited after the entry was built, and long enough to
+FT                   carry on over a second line"
+FT                   /codon_start=1
+FT                   /translation="MACDEFGHIKLMNPQRSTVWYACDEFGHIKLMNPQRSTVWYACDE
+FT                   FGHIKLMNPQRSTVW"
+XX
+SQ   Sequence 190 BP; 45 A; 45 C; 45 G; 45 T; 10 other;
+     acgtacgtac gtacgtacgt acgtacgtac gtacgtacgt acgtacgtac gtacgtacgt        60
+     acgtacgtac gtacgtacgt acgtacgtac gtacgtacgt acgtacgtac gtacgtacgt       120
+     acgtacgtac gtacgtacgt acgtacgtac gtacgtacgt acgtacgtac gtacgtacgt       180
+     nnnnnnnnnn                                                              190
+//
+"#;
+
+  #[test]
+  fn test_write_built_entry() {
+    let annotation = |name: &str, value: &str| Annotation { name: name.to_string(), values: vec![value.to_string()] };
+    let qualifier = |name: &str, value: QualifierValue| Qualifier { name: FtString::from_str(name).unwrap(), value: Some(value) };
+    let quoted = |name: &str, text: &str| qualifier(name, QualifierValue::QuotedText(text.to_string()));
+    let span = |from, to, after_to| LocOp::Loc(Loc::Local(match Local::span(from, to) {
+      Local::Span { from, to, before_from, .. } => Local::Span { from, to, before_from, after_to },
+      l => l,
+    }));
+
+    let mut e = Embl {
+      annotations: vec![
+        annotation("ID", "AB000002; SV 1; linear; mRNA; STD; PLN; 0 BP."),
+        annotation("AC", "AB000002;"),
+        annotation("DE", "Made-up mRNA, written from an entry built in code rather than read from a flat file"),
+      ],
+      features: FeatureTable { features: vec![
+        FeatureRecord { key: "source".to_string(), location: span(1, 190, false), qualifiers: vec![
+          quoted("organism", "Trifolium repens"),
+          quoted("mol_type", "mRNA"),
+        ] },
+        FeatureRecord { key: "CDS".to_string(), location: LocOp::Join(vec![span(1, 30, false), span(41, 190, true)]), qualifiers: vec![
+          quoted("note", "draft"),
+          qualifier("codon_start", QualifierValue::Unquoted("1".to_string())),
+          quoted("translation", &format!("M{}", &"ACDEFGHIKLMNPQRSTVWY".repeat(3)[..59])),
+        ] },
+      ] },
+      sequence: format!("{}{}", "acgt".repeat(45), "n".repeat(10)),
+    };
+
+    let note = e.features.features[1].qualifiers.iter_mut().find(|q| q.name.as_str() == "note").unwrap();
+    note.value = Some(QualifierValue::QuotedText(
+      "edited after the entry was built, and long enough to carry on over a second line".to_string()));
+
+    let mut out = Vec::new();
+    e.write(&mut out).unwrap();
+    let written = String::from_utf8(out).unwrap();
+    assert_eq!(written, BUILT);
+    assert!(written.lines().all(|l| l.len() <= LINE_WIDTH));
+
+    let read = Embl::from_str(&written).unwrap();
+    assert_eq!(read.features, e.features);
+    assert_eq!(read.sequence, e.sequence);
+  }
+
+  /// The flat files in `testdata/embl`, by name.
+  fn test_files() -> Vec<(String, String)> {
+    let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/testdata/embl");
+    let mut paths: Vec<_> = std::fs::read_dir(dir).unwrap()
+      .map(|e| e.unwrap().path())
+      .filter(|p| p.extension().is_some_and(|x| x == "embl"))
+      .collect();
+    paths.sort();
+    paths.iter()
+      .map(|p| (p.file_name().unwrap().to_string_lossy().into_owned(), std::fs::read_to_string(p).unwrap()))
+      .collect()
+  }
+
+  fn read_file(name: &str, text: &str) -> Vec<Embl> {
+    EmblReader::new(text.as_bytes()).collect::<Result<Vec<_>, _>>()
+      .unwrap_or_else(|e| panic!("{}: {}", name, e))
+  }
+
+  #[test]
+  fn test_parse_files() {
+    let files = test_files();
+    assert!(files.len() >= 3);
+    for (name, text) in &files {
+      let entries = read_file(name, text);
+      assert!(!entries.is_empty(), "{} has no entries", name);
+      for e in &entries {
+        assert_eq!(e.id(), e.accession(), "{}", name);
+        assert!(e.description().is_some(), "{}", name);
+        if !e.sequence.is_empty() {
+          let length = format!(" {} BP.", e.sequence.len());
+          assert!(e.annotation("ID").unwrap().values[0].ends_with(&length), "{}", name);
+        }
+      }
+    }
+
+    let file = |name: &str| {
+      let (name, text) = files.iter().find(|(n, _)| n == name).unwrap();
+      read_file(name, text).remove(0)
+    };
+
+    let e = file("x56734_multiple_references.embl");
+    assert_eq!(e.annotations.iter().filter(|a| a.name == "RN").count(), 3);
+    assert_eq!(e.annotations.iter().filter(|a| a.name == "RX").map(|a| a.values.len()).collect::<Vec<_>>(), vec![2]);
+    assert_eq!(e.annotations.iter().filter(|a| a.name == "RL").map(|a| a.values.len()).collect::<Vec<_>>(), vec![1, 3, 1]);
+    assert_eq!(e.sequence.len(), 1859);
+
+    let e = file("cm000001_con.embl");
+    assert!(e.sequence.is_empty());
+    assert_eq!(e.annotation("CO").unwrap().values.concat(),
+      "join(AAAA01000001.1:1..1240000,gap(100),AAAA01000002.1:1..856212,gap(unk100),complement(AAAA01000003.1:1..417064))");
+    assert_eq!(e.features.features[1].key, "assembly_gap");
+
+    let e = file("oz000001_long_translation.embl");
+    let translation = value(&e, 2, "translation").unwrap().text();
+    assert_eq!(translation.len(), 712);
+    assert!(translation.starts_with('M') && translation.chars().all(|c| c.is_ascii_uppercase()));
+    assert_eq!(value(&e, 2, "note").unwrap().text(),
+      "see https://www.ebi.ac.uk/ena/browser/api/embl/OZ000001?lineLimit=1000&download=true for the entry");
+  }
+
+  #[test]
+  fn test_files_round_trip() {
+    for (name, text) in test_files() {
+      let entries = read_file(&name, &text);
+      let written: String = entries.iter().map(Embl::to_string).collect();
+      assert_eq!(read_file(&name, &written), entries, "{}", name);
+      assert_eq!(written, text, "{}", name);
+    }
+  }
+}
@@ -0,0 +1,192 @@
+//! # Parse errors
+//!
+//! A common error for malformed records in any of the sequence and annotation
+//! formats, locating the problem by file, line, column and record, and the
+//! error and line reader shared by the streaming readers of those formats.
+
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::io::{self, BufRead};
+
+use crate::alphabet::InvalidResidue;
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ParseError {
+  /// Name of the input, if known.
+  pub file: Option<String>,
+  /// Line of the input, counting from 1.
+  pub line: usize,
+  /// Column within the line, counting from 1.
+  pub column: Option<usize>,
+  /// Record within the input, counting from 1. `None` for input outside of
+  /// any record.
+  pub record: Option<usize>,
+  pub message: String,
+}
+
+impl ParseError {
+  pub fn new<S : Into<String>>(line: usize, message: S) -> ParseError {
+    ParseError { file: None, line, column: None, record: None, message: message.into() }
+  }
+
+  pub fn with_file<S : Into<String>>(self, file: S) -> ParseError {
+    ParseError { file: Some(file.into()), ..self }
+  }
+
+  pub fn with_column(self, column: usize) -> ParseError {
+    ParseError { column: Some(column), ..self }
+  }
+
+  pub fn with_record(self, record: usize) -> ParseError {
+    ParseError { record: Some(record), ..self }
+  }
+
+  /// Report a residue rejected by a [crate::alphabet::Validator] as part of
+  /// record number `record`.
+  pub fn invalid_residue(e: &InvalidResidue, record: usize) -> ParseError {
+    ParseError::new(e.line, format!("residue `{}' in `{}' is not valid {:?}",
+        e.residue.escape_default(), e.id, e.alphabet))
+      .with_column(e.column)
+      .with_record(record)
+  }
+}
+
+impl Display for ParseError {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    match &self.file {
+      Some(file) => write!(f, "{}:{}", file, self.line)?,
+      None => write!(f, "line {}", self.line)?,
+    }
+    if let Some(c) = self.column {
+      write!(f, ":{}", c)?;
+    }
+    if let Some(r) = self.record {
+      write!(f, " (record {})", r)?;
+    }
+    write!(f, ": {}", self.message)
+  }
+}
+
+impl Error for ParseError {}
+
+impl From<ParseError> for io::Error {
+  fn from(e: ParseError) -> Self {
+    io::Error::new(io::ErrorKind::InvalidData, e.to_string())
+  }
+}
+
+
+/// Errors raised while streaming records with one of the format readers.
+#[derive(Debug)]
+pub enum ReadError {
+  /// The underlying reader failed.
+  Io(io::Error),
+  /// A record is malformed. Reading can carry on with the next record.
+  Parse(ParseError),
+}
+
+impl Display for ReadError {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    match self {
+      ReadError::Io(e) => write!(f, "Unable to read input because: {}", e),
+      ReadError::Parse(e) => write!(f, "Unable to parse input at {}", e),
+    }
+  }
+}
+
+impl Error for ReadError {
+  fn source(&self) -> Option<&(dyn Error + 'static)> {
+    match self {
+      ReadError::Io(e) => Some(e),
+      ReadError::Parse(e) => Some(e),
+    }
+  }
+}
+
+impl From<io::Error> for ReadError {
+  fn from(e: io::Error) -> Self { ReadError::Io(e) }
+}
+
+impl From<ParseError> for ReadError {
+  fn from(e: ParseError) -> Self { ReadError::Parse(e) }
+}
+
+impl From<ReadError> for io::Error {
+  fn from(e: ReadError) -> Self {
+    match e {
+      ReadError::Io(e) => e,
+      ReadError::Parse(e) => e.into(),
+    }
+  }
+}
+
+
+/// Reads input a line at a time over the raw bytes, counting lines so that
+/// errors can be located.
+pub struct LineReader<R> {
+  reader: R,
+  line: Vec<u8>,
+  line_no: usize,
+}
+
+impl <R : BufRead> LineReader<R> {
+  pub fn new(reader: R) -> LineReader<R> {
+    LineReader { reader, line: Vec::new(), line_no: 0 }
+  }
+
+  /// Read the next line, keeping its line ending, returning false at end of
+  /// input.
+  pub fn read_line(&mut self) -> Result<bool, io::Error> {
+    self.line.clear();
+    loop {
+      match self.reader.read_until(b'\n', &mut self.line) {
+        Ok(0) => return Ok(false),
+        Ok(_) => { self.line_no += 1; return Ok(true) },
+        Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+        Err(e) => return Err(e),
+      }
+    }
+  }
+
+  /// The line last read.
+  pub fn line(&self) -> &[u8] {
+    &self.line
+  }
+
+  pub fn line_mut(&mut self) -> &mut Vec<u8> {
+    &mut self.line
+  }
+
+  /// The number of the line last read, counting from 1.
+  pub fn line_no(&self) -> usize {
+    self.line_no
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+
+  #[test]
+  fn test_display() {
+    let e = ParseError::new(12, "expected a `+` line");
+    assert_eq!(e.to_string(), "line 12: expected a `+` line");
+
+    let e = e.with_file("reads.fq").with_column(3).with_record(4);
+    assert_eq!(e.to_string(), "reads.fq:12:3 (record 4): expected a `+` line");
+  }
+
+  #[test]
+  fn test_line_reader() {
+    let mut lines = LineReader::new("one\r\ntwo\n\nthree".as_bytes());
+    let mut read = Vec::new();
+    while lines.read_line().unwrap() {
+      read.push((lines.line_no(), String::from_utf8_lossy(lines.line()).into_owned()));
+    }
+    assert_eq!(read, [(1, "one\r\n".to_string()), (2, "two\n".to_string()), (3, "\n".to_string()), (4, "three".to_string())]);
+    assert!(!lines.read_line().unwrap());
+    assert_eq!(lines.line_no(), 4);
+  }
+}
@@ -1,4 +1,13 @@
-use std::io;
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::io::{self, BufRead, Read, Seek, SeekFrom};
+use std::str::FromStr;
+
+use crate::alphabet::{InvalidResidue, Validator};
+
+use super::error::{LineReader, ParseError};
 
 extern crate nom;
 use nom::{
@@ -54,7 +63,7 @@ impl FastaDescription {
   }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct FastaRecord {
   pub descr_line: String,
   pub seq: String,
@@ -70,18 +79,27 @@ impl FastaRecord {
   }
 
   pub fn write<W : io::Write>(&self, format: &FastaFormat, out: &mut W) -> Result<(), io::Error> {
-    let ll = format.line_length;
+    let eol = format.line_ending.as_str();
 
-    write!(out, ">")?;
-    writeln!(out, "{}", self.descr_line)?;
+    match &format.header_rewrite {
+      Some(rewrite) => write!(out, ">{}{}", rewrite(&self.descr_line), eol)?,
+      None => write!(out, ">{}{}", self.descr_line, eol)?,
+    }
 
-    let l = self.seq.len();
-    //let overhang = l % ll;
+    let seq = match format.case {
+      Case::Preserve => Cow::Borrowed(self.seq.as_bytes()),
+      Case::Upper => Cow::Owned(self.seq.to_ascii_uppercase().into_bytes()),
+      Case::Lower => Cow::Owned(self.seq.to_ascii_lowercase().into_bytes()),
+    };
+
+    let l = seq.len();
+    let ll = if format.line_length == 0 { l.max(1) } else { format.line_length };
 
     for o in (0..l).step_by(ll) {
       let e = o + ll;
       let r = if e > l { l } else { e };
-      writeln!(out, "{}", &self.seq[o..r])?;
+      out.write_all(&seq[o..r])?;
+      write!(out, "{}", eol)?;
     }
 
     Ok(())
@@ -169,23 +187,506 @@ pub fn parse_fastas(input: &str) -> IResult<&str, Vec<FastaRecord>> {
   many0(parse_fasta)(input)
 }
 
+/// Options controlling how [FastaRecord]s are written.
 #[derive(Default)]
 pub struct FastaFormat {
+  /// Bases per sequence line. Zero writes each sequence on a single line.
   pub line_length: usize,
+  pub case: Case,
+  pub line_ending: LineEnding,
+  /// Applied to each description line before it is written.
+  pub header_rewrite: Option<HeaderRewrite>,
 }
 
+pub type HeaderRewrite = Box<dyn Fn(&str) -> String>;
+
 impl FastaFormat {
   pub fn new() -> FastaFormat {
-    FastaFormat { line_length: 60 }
+    FastaFormat { line_length: 60, ..Default::default() }
+  }
+
+  pub fn with_line_length(self, line_length: usize) -> FastaFormat {
+    FastaFormat { line_length, ..self }
+  }
+
+  pub fn with_case(self, case: Case) -> FastaFormat {
+    FastaFormat { case, ..self }
+  }
+
+  pub fn with_line_ending(self, line_ending: LineEnding) -> FastaFormat {
+    FastaFormat { line_ending, ..self }
+  }
+
+  pub fn with_header_rewrite<F>(self, rewrite: F) -> FastaFormat
+  where F : Fn(&str) -> String + 'static
+  {
+    FastaFormat { header_rewrite: Some(Box::new(rewrite)), ..self }
+  }
+}
+
+/// Case conversion applied to written sequences.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum Case {
+  #[default]
+  Preserve,
+  Upper,
+  Lower,
+}
+
+impl FromStr for Case {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "preserve" => Ok(Case::Preserve),
+      "upper" => Ok(Case::Upper),
+      "lower" => Ok(Case::Lower),
+      e => Err(format!("Unknown case `{}'. Expected preserve, upper or lower", e)),
+    }
+  }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum LineEnding {
+  #[default]
+  Lf,
+  CrLf,
+}
+
+impl LineEnding {
+  pub fn as_str(self) -> &'static str {
+    match self {
+      LineEnding::Lf => "\n",
+      LineEnding::CrLf => "\r\n",
+    }
+  }
+}
+
+
+/// Errors raised while streaming FASTA records with a [FastaReader].
+#[derive(Debug)]
+pub enum FastaError {
+  /// The underlying reader failed.
+  Io(io::Error),
+  /// A record isn't laid out as FASTA, or holds a residue rejected by a
+  /// [Validator]. Reading can carry on with the next record.
+  Parse(ParseError),
+  /// A region couldn't be fetched using a [FastaIndex].
+  Region(String),
+}
+
+impl Display for FastaError {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    match self {
+      FastaError::Io(e) => write!(f, "Unable to read fasta input because: {}", e),
+      FastaError::Parse(e) => write!(f, "Unable to parse fasta input at {}", e),
+      FastaError::Region(message) => write!(f, "Unable to fetch fasta region: {}", message),
+    }
+  }
+}
+
+impl Error for FastaError {
+  fn source(&self) -> Option<&(dyn Error + 'static)> {
+    match self {
+      FastaError::Io(e) => Some(e),
+      FastaError::Parse(e) => Some(e),
+      FastaError::Region(_) => None,
+    }
+  }
+}
+
+impl From<io::Error> for FastaError {
+  fn from(e: io::Error) -> Self { FastaError::Io(e) }
+}
+
+impl From<FastaError> for io::Error {
+  fn from(e: FastaError) -> Self {
+    match e {
+      FastaError::Io(e) => e,
+      e => io::Error::new(io::ErrorKind::InvalidData, e.to_string()),
+    }
+  }
+}
+
+/// Streaming FASTA reader, yielding one record at a time.
+///
+/// This works line-by-line over the raw bytes, so only the current record is
+/// held in memory. Input doesn't need to be valid UTF-8: invalid sequences are
+/// replaced with `U+FFFD` rather than failing the whole record.
+///
+/// Sequence lines can be checked against an alphabet as they are read, using
+/// [FastaReader::with_validator].
+pub struct FastaReader<R> {
+  lines: LineReader<R>,
+  next_header: Option<String>,
+  record: usize,
+  validator: Option<Validator>,
+  warnings: Vec<InvalidResidue>,
+  done: bool,
+}
+
+impl <R : BufRead> FastaReader<R> {
+  pub fn new(reader: R) -> FastaReader<R> {
+    FastaReader {
+      lines: LineReader::new(reader),
+      next_header: None,
+      record: 0,
+      validator: None,
+      warnings: Vec::new(),
+      done: false,
+    }
+  }
+
+  pub fn with_validator(self, validator: Option<Validator>) -> FastaReader<R> {
+    FastaReader { validator, ..self }
+  }
+
+  /// Take the residues kept under [crate::alphabet::Policy::Warn] since this
+  /// was last called.
+  pub fn take_warnings(&mut self) -> Vec<InvalidResidue> {
+    std::mem::take(&mut self.warnings)
+  }
+
+  /// The description text of the current line, if it is a header line.
+  fn header(&self) -> Option<String> {
+    let l = trim_line_end(self.lines.line());
+    let start = l.iter().position(|&c| !(c == b' ' || c == b'\t'))?;
+    if l[start] == b'>' {
+      Some(String::from_utf8_lossy(&l[start + 1..]).into_owned())
+    } else {
+      None
+    }
+  }
+
+  /// Skip lines up to the next header, so that reading can carry on after an error.
+  fn skip_to_header(&mut self) -> Result<(), io::Error> {
+    while self.lines.read_line()? {
+      if let Some(h) = self.header() {
+        self.next_header = Some(h);
+        break;
+      }
+    }
+    Ok(())
+  }
+
+  fn read_record(&mut self) -> Result<Option<FastaRecord>, FastaError> {
+    let descr_line = match self.next_header.take() {
+      Some(h) => h,
+      None => loop {
+        if !self.lines.read_line()? { return Ok(None) }
+        if let Some(h) = self.header() { break h }
+        if !is_blank(self.lines.line()) {
+          let e = ParseError::new(self.lines.line_no(), "expected a `>` description line before sequence data");
+          self.skip_to_header()?;
+          return Err(FastaError::Parse(e))
+        }
+      }
+    };
+    self.record += 1;
+
+    // on an invalid residue, read the rest of the record so the next one can be read
+    let id = descr_line.split_whitespace().next().unwrap_or("").to_string();
+    let mut invalid = None;
+    let mut seq = Vec::new();
+    while self.lines.read_line()? {
+      if let Some(h) = self.header() {
+        self.next_header = Some(h);
+        break;
+      }
+      if let (Some(v), None) = (&self.validator, &invalid) {
+        match v.check_line(&id, self.lines.line_no(), self.lines.line_mut()) {
+          Ok(w) => self.warnings.extend(w),
+          Err(e) => invalid = Some(ParseError::invalid_residue(&e, self.record)),
+        }
+      }
+      seq.extend(self.lines.line().iter().filter(|c| !is_fasta_space(**c)));
+    }
+    if let Some(e) = invalid {
+      return Err(FastaError::Parse(e));
+    }
+
+    let seq = String::from_utf8(seq)
+      .unwrap_or_else(|e| String::from_utf8_lossy(e.as_bytes()).into_owned());
+    Ok(Some(FastaRecord { descr_line, seq }))
+  }
+}
+
+impl <R : BufRead> Iterator for FastaReader<R> {
+  type Item = Result<FastaRecord, FastaError>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    if self.done { return None }
+    match self.read_record() {
+      Ok(Some(r)) => Some(Ok(r)),
+      Ok(None) => { self.done = true; None },
+      Err(e @ FastaError::Parse(_)) => Some(Err(e)),
+      Err(e) => { self.done = true; Some(Err(e)) },
+    }
+  }
+}
+
+fn is_fasta_space(c: u8) -> bool {
+  c == b' ' || c == b'\t' || c == b'\r' || c == b'\n'
+}
+
+fn is_blank(line: &[u8]) -> bool {
+  line.iter().all(|c| is_fasta_space(*c))
+}
+
+fn trim_line_end(line: &[u8]) -> &[u8] {
+  let mut l = line;
+  while let Some((c, rest)) = l.split_last() {
+    if *c == b'\n' || *c == b'\r' { l = rest } else { break }
+  }
+  l
+}
+
+
+/// A single line of a samtools-compatible `.fai` index.
+#[derive(Debug, PartialEq, Clone)]
+pub struct FaiRecord {
+  /// The identifier of the sequence, up to the first whitespace in its header.
+  pub name: String,
+  /// The number of bases in the sequence.
+  pub length: u64,
+  /// Byte offset of the first base of the sequence.
+  pub offset: u64,
+  /// The number of bases on each full sequence line.
+  pub line_bases: u64,
+  /// The number of bytes on each full sequence line, including its line ending.
+  pub line_width: u64,
+}
+
+impl FaiRecord {
+  /// Byte offset of the base at `pos`, counting from 0.
+  pub fn byte_offset(&self, pos: u64) -> u64 {
+    match pos.checked_div(self.line_bases) {
+      Some(line) => self.offset + line * self.line_width + pos % self.line_bases,
+      None => self.offset,
+    }
+  }
+}
+
+/// An index over a FASTA file, allowing regions to be fetched by seeking
+/// rather than scanning the whole file.
+///
+/// This reads and writes the same `.fai` format as `samtools faidx`.
+#[derive(Debug, Default)]
+pub struct FastaIndex {
+  records: Vec<FaiRecord>,
+  by_name: HashMap<String, usize>,
+}
+
+impl FastaIndex {
+  pub fn new(records: Vec<FaiRecord>) -> FastaIndex {
+    let by_name = records.iter().enumerate().map(|(i, r)| (r.name.clone(), i)).collect();
+    FastaIndex { records, by_name }
+  }
+
+  pub fn records(&self) -> &[FaiRecord] {
+    &self.records
+  }
+
+  pub fn get(&self, name: &str) -> Option<&FaiRecord> {
+    self.by_name.get(name).map(|i| &self.records[*i])
+  }
+
+  /// Index FASTA input.
+  ///
+  /// Records are found as chunks starting at a `>` at the beginning of a line,
+  /// and their offsets come from the chunk offsets. As with samtools, every
+  /// sequence line but the last in a record must have the same length.
+  pub fn build<R : BufRead>(reader: R) -> Result<FastaIndex, FastaError> {
+    let delim = chunks::Delim::new(b"\n>", false);
+    let mut chunker = chunks::chunks(reader, &delim);
+    let mut records = Vec::new();
+    let mut line_no = 1;
+
+    while let Some(chunk) = chunker.next() {
+      let chunk = chunk?;
+      let mut offset = chunker.offset();
+      let mut c = &chunk[..];
+      if c.first() == Some(&b'\n') {
+        c = &c[1..];
+        offset += 1;
+        line_no += 1;
+      }
+
+      if c.first() != Some(&b'>') {
+        if is_blank(c) {
+          line_no += count_lines(c);
+          continue
+        }
+        return Err(FastaError::Parse(ParseError::new(
+          line_no, "expected a `>` description line before sequence data")))
+      }
+
+      // a record with no sequence has its header's newline in the next chunk
+      let newline = c.iter().position(|b| *b == b'\n');
+      let header_end = newline.map_or(c.len(), |p| p + 1);
+      let header = trim_line_end(&c[1..header_end]);
+      let name = String::from_utf8_lossy(header)
+        .split_whitespace().next().unwrap_or("").to_string();
+      let eol = if newline.is_some() { (header_end - 1 - header.len()) as u64 } else { 1 };
+
+      let mut rec = FaiRecord {
+        name,
+        length: 0,
+        offset: offset + header_end as u64 + if newline.is_some() { 0 } else { eol },
+        line_bases: 0,
+        line_width: 0,
+      };
+      let header_line = line_no;
+      let mut last_short = None;
+
+      for (i, line) in c[header_end..].split(|b| *b == b'\n').enumerate() {
+        let bases = trim_line_end(line).len() as u64;
+        if bases == 0 { continue }
+        let line_at = header_line + 1 + i;
+        if let Some(short_at) = last_short {
+          return Err(FastaError::Parse(ParseError::new(
+            short_at, format!("sequence `{}` has lines of different lengths", rec.name))
+            .with_record(records.len() + 1)))
+        }
+        if rec.line_bases == 0 {
+          rec.line_bases = bases;
+          rec.line_width = bases + eol;
+        } else if bases > rec.line_bases {
+          return Err(FastaError::Parse(ParseError::new(
+            line_at, format!("sequence `{}` has lines of different lengths", rec.name))
+            .with_record(records.len() + 1)))
+        }
+        if bases < rec.line_bases { last_short = Some(line_at) }
+        rec.length += bases;
+      }
+
+      line_no += count_lines(c);
+      records.push(rec);
+    }
+
+    Ok(FastaIndex::new(records))
   }
 
-  pub fn _new_with_line_length(line_length: usize) -> FastaFormat {
-    FastaFormat { line_length }
+  /// Read a `.fai` index.
+  pub fn read<R : BufRead>(reader: R) -> Result<FastaIndex, FastaError> {
+    let mut records = Vec::new();
+    for (i, line) in reader.lines().enumerate() {
+      let line = line?;
+      if line.trim().is_empty() { continue }
+      let format_error = |message: &str| FastaError::Parse(ParseError::new(i + 1, message));
+
+      let mut columns = line.split('\t');
+      let name = columns.next().ok_or_else(|| format_error("no name column"))?.to_string();
+      let mut number = |column: &str| columns.next()
+        .ok_or_else(|| format_error(&format!("no {} column", column)))
+        .and_then(|n| u64::from_str(n.trim()).map_err(|e| format_error(&format!("bad {} column: {}", column, e))));
+
+      let length = number("length")?;
+      let offset = number("offset")?;
+      let line_bases = number("line bases")?;
+      let line_width = number("line width")?;
+      records.push(FaiRecord { name, length, offset, line_bases, line_width });
+    }
+
+    Ok(FastaIndex::new(records))
+  }
+
+  /// Write the index in `.fai` format.
+  pub fn write<W : io::Write>(&self, out: &mut W) -> Result<(), io::Error> {
+    for r in &self.records {
+      writeln!(out, "{}\t{}\t{}\t{}\t{}", r.name, r.length, r.offset, r.line_bases, r.line_width)?;
+    }
+    Ok(())
+  }
+
+  /// Fetch the bases of a region from the indexed FASTA file.
+  ///
+  /// As with samtools, an end past the end of the sequence is clipped to the
+  /// sequence length.
+  pub fn fetch<R : Read + Seek>(&self, reader: &mut R, region: &Region) -> Result<String, FastaError> {
+    let rec = self.get(&region.name)
+      .ok_or_else(|| FastaError::Region(format!("no sequence named `{}` in the index", region.name)))?;
+
+    let start = region.start.unwrap_or(1);
+    let end = region.end.unwrap_or(rec.length).min(rec.length);
+    if start < 1 || start > end {
+      return Err(FastaError::Region(format!(
+        "`{}` is not within sequence `{}` of length {}", region, rec.name, rec.length)));
+    }
+
+    let from = rec.byte_offset(start - 1);
+    let to = rec.byte_offset(end - 1) + 1;
+    reader.seek(SeekFrom::Start(from))?;
+    let mut bytes = Vec::with_capacity((to - from) as usize);
+    reader.take(to - from).read_to_end(&mut bytes)?;
+    bytes.retain(|b| !is_fasta_space(*b));
+
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+  }
+}
+
+fn count_lines(bytes: &[u8]) -> usize {
+  bytes.iter().filter(|b| **b == b'\n').count()
+}
+
+
+/// A region of a named sequence, as used by `samtools faidx`.
+///
+/// Written as `name`, `name:start` or `name:start-end`, with 1-based,
+/// inclusive coordinates. Numbers may contain commas, as in `chr1:1,000-2,000`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Region {
+  pub name: String,
+  pub start: Option<u64>,
+  pub end: Option<u64>,
+}
+
+impl Region {
+  /// Parse a region of a sequence in `index`. As with samtools, a name in the
+  /// index is taken whole, even if it looks like `name:start-end`, so names
+  /// holding `:` can be fetched.
+  pub fn parse(s: &str, index: &FastaIndex) -> Result<Region, String> {
+    match index.get(s.trim()) {
+      Some(rec) => Ok(Region { name: rec.name.clone(), start: None, end: None }),
+      None => Region::from_str(s),
+    }
+  }
+}
+
+impl FromStr for Region {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let s = s.trim();
+    let number = |n: &str| u64::from_str(&n.replace(',', ""))
+      .map_err(|e| format!("Cannot parse `{}` in region `{}`: {}", n, s, e));
+
+    match s.rfind(':') {
+      None => Ok(Region { name: s.to_string(), start: None, end: None }),
+      Some(c) => {
+        let (name, range) = (&s[..c], &s[c + 1..]);
+        let (start, end) = match range.find('-') {
+          None => (number(range)?, None),
+          Some(d) => (number(&range[..d])?, Some(number(&range[d + 1..])?)),
+        };
+        Ok(Region { name: name.to_string(), start: Some(start), end })
+      }
+    }
+  }
+}
+
+impl Display for Region {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    write!(f, "{}", self.name)?;
+    if let Some(s) = self.start { write!(f, ":{}", s)? }
+    if let Some(e) = self.end { write!(f, "-{}", e)? }
+    Ok(())
   }
 }
 
 
 
+
 #[cfg(test)]
 mod tests {
 
@@ -352,6 +853,173 @@ mod tests {
     assert_eq!(parse_fastas(input), Ok((rem, vec![output])));
   }
 
+  #[test]
+  fn test_write_wrapped() {
+    let rec = FastaRecord { descr_line: "id descr".to_string(), seq: "acgtACGTac".to_string() };
+    let mut out = Vec::new();
+    rec.write(&FastaFormat::new().with_line_length(4), &mut out).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), ">id descr\nacgt\nACGT\nac\n");
+  }
+
+  #[test]
+  fn test_write_unwrapped_upper_crlf() {
+    let rec = FastaRecord { descr_line: "id descr".to_string(), seq: "acgtACGTac".to_string() };
+    let format = FastaFormat::new()
+      .with_line_length(0)
+      .with_case(Case::Upper)
+      .with_line_ending(LineEnding::CrLf)
+      .with_header_rewrite(|h| format!("x_{}", h));
+    let mut out = Vec::new();
+    rec.write(&format, &mut out).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), ">x_id descr\r\nACGTACGTAC\r\n");
+  }
+
+  #[test]
+  fn test_fasta_reader_records() {
+    let input: &[u8] = b">id1 descr\natgcatgcgtcgtatcgta\ngcgtcgatctgca\n>id2\r\nacgt\r\n\n>id3 empty\n";
+    let records: Vec<FastaRecord> = FastaReader::new(input).map(Result::unwrap).collect();
+    assert_eq!(records, vec![
+      FastaRecord { descr_line: "id1 descr".to_string(), seq: "atgcatgcgtcgtatcgtagcgtcgatctgca".to_string() },
+      FastaRecord { descr_line: "id2".to_string(), seq: "acgt".to_string() },
+      FastaRecord { descr_line: "id3 empty".to_string(), seq: "".to_string() },
+    ]);
+  }
+
+  #[test]
+  fn test_fasta_reader_validation() {
+    use crate::alphabet::{Alphabet, Policy};
+
+    let input: &[u8] = b">id1 descr\nacgt\nacgx\n";
+    let strict = Some(Validator::new(Alphabet::Dna, Policy::Strict));
+    match FastaReader::new(input).with_validator(strict).next() {
+      Some(Err(FastaError::Parse(e))) => assert_eq!((e.line, e.column, e.record), (3, Some(4), Some(1))),
+      r => panic!("Expected an invalid residue error but got {:?}", r)
+    }
+
+    let replace = Some(Validator::new(Alphabet::Dna, Policy::Replace(b'n')));
+    let records: Vec<FastaRecord> = FastaReader::new(input).with_validator(replace).map(Result::unwrap).collect();
+    assert_eq!(records[0].seq, "acgtacgn");
+
+    let warn = Some(Validator::new(Alphabet::Dna, Policy::Warn));
+    let mut reader = FastaReader::new(input).with_validator(warn);
+    assert_eq!(reader.next().unwrap().unwrap().seq, "acgtacgx");
+    let warnings = reader.take_warnings();
+    assert_eq!(warnings.iter().map(|w| (w.line, w.column)).collect::<Vec<_>>(), vec![(3, 4)]);
+    assert!(reader.take_warnings().is_empty());
+  }
+
+  #[test]
+  fn test_fasta_reader_empty() {
+    let input: &[u8] = b"\n\n";
+    assert_eq!(FastaReader::new(input).count(), 0);
+  }
+
+  #[test]
+  fn test_fasta_reader_invalid_utf8() {
+    let input: &[u8] = b">id \xff\nac\xfegt\n";
+    let records: Vec<FastaRecord> = FastaReader::new(input).map(Result::unwrap).collect();
+    assert_eq!(records, vec![
+      FastaRecord { descr_line: "id \u{FFFD}".to_string(), seq: "ac\u{FFFD}gt".to_string() },
+    ]);
+  }
+
+  #[test]
+  fn test_fasta_reader_sequence_before_header() {
+    let input: &[u8] = b"\nacgt\n>id\nacgt\n";
+    match FastaReader::new(input).next() {
+      Some(Err(FastaError::Parse(e))) => assert_eq!((e.line, e.record), (2, None)),
+      r => panic!("Expected a format error but got {:?}", r)
+    }
+  }
+
+  #[test]
+  fn test_fasta_reader_recovers() {
+    use crate::alphabet::{Alphabet, Policy};
+
+    let input: &[u8] = b"acgt
+>id1
+ac
+>id2
+axxa
+acgt
+>id3
+acgt
+";
+    let strict = Some(Validator::new(Alphabet::Dna, Policy::Strict));
+    let results: Vec<Result<FastaRecord, FastaError>> = FastaReader::new(input).with_validator(strict).collect();
+    assert_eq!(results.len(), 4);
+    assert!(results[0].is_err());
+    assert_eq!(results[1].as_ref().unwrap().seq, "ac");
+    match &results[2] {
+      Err(FastaError::Parse(e)) => assert_eq!((e.line, e.column, e.record), (5, Some(2), Some(2))),
+      r => panic!("Expected an invalid residue error but got {:?}", r)
+    }
+    assert_eq!(results[3].as_ref().unwrap().descr_line, "id3");
+  }
+
+  #[test]
+  fn test_fasta_index_build() {
+    let input: &[u8] = b">one first\nACGTA\nCGTAC\nGT\n>two\r\nAAAA\r\nCC\r\n>empty\n>three\nACG\n";
+    let index = FastaIndex::build(input).unwrap();
+    assert_eq!(index.records(), &[
+      FaiRecord { name: "one".to_string(), length: 12, offset: 11, line_bases: 5, line_width: 6 },
+      FaiRecord { name: "two".to_string(), length: 6, offset: 32, line_bases: 4, line_width: 6 },
+      FaiRecord { name: "empty".to_string(), length: 0, offset: 49, line_bases: 0, line_width: 0 },
+      FaiRecord { name: "three".to_string(), length: 3, offset: 56, line_bases: 3, line_width: 4 },
+    ][..]);
+
+    let mut fai = Vec::new();
+    index.write(&mut fai).unwrap();
+    assert_eq!(FastaIndex::read(&fai[..]).unwrap().records(), index.records());
+  }
+
+  #[test]
+  fn test_fasta_index_ragged_lines() {
+    let input: &[u8] = b">one\nACGTA\nCG\nACGTA\n";
+    match FastaIndex::build(input) {
+      Err(FastaError::Parse(e)) => assert_eq!((e.line, e.record), (3, Some(1))),
+      r => panic!("Expected a format error but got {:?}", r)
+    }
+  }
+
+  #[test]
+  fn test_fasta_index_fetch() {
+    let input: &[u8] = b">one first\nACGTA\nCGTAC\nGT\n>two\r\nAAAA\r\nCC\r\n";
+    let index = FastaIndex::build(input).unwrap();
+    let mut file = io::Cursor::new(input);
+    let fetch = |file: &mut io::Cursor<&[u8]>, r: &str| index.fetch(file, &r.parse().unwrap());
+
+    assert_eq!(fetch(&mut file, "one").unwrap(), "ACGTACGTACGT");
+    assert_eq!(fetch(&mut file, "one:4-7").unwrap(), "TACG");
+    assert_eq!(fetch(&mut file, "one:11").unwrap(), "GT");
+    assert_eq!(fetch(&mut file, "one:10-100").unwrap(), "CGT");
+    assert_eq!(fetch(&mut file, "two:3-6").unwrap(), "AACC");
+    assert!(fetch(&mut file, "three").is_err());
+    assert!(fetch(&mut file, "two:7-8").is_err());
+  }
+
+  #[test]
+  fn test_parse_region() {
+    assert_eq!("chr1".parse(), Ok(Region { name: "chr1".to_string(), start: None, end: None }));
+    assert_eq!("chr1:1,000-2,000".parse(), Ok(Region { name: "chr1".to_string(), start: Some(1000), end: Some(2000) }));
+    assert_eq!("HLA:A:10".parse(), Ok(Region { name: "HLA:A".to_string(), start: Some(10), end: None }));
+    assert!("chr1:x-2".parse::<Region>().is_err());
+  }
+
+  #[test]
+  fn test_parse_region_in_index() {
+    let input: &[u8] = b">HLA:01:02\nACGTACGT\n>HLA:01\nTTTT\n";
+    let index = FastaIndex::build(input).unwrap();
+    let mut file = io::Cursor::new(input);
+    let fetch = |file: &mut io::Cursor<&[u8]>, r: &str| index.fetch(file, &Region::parse(r, &index).unwrap());
+
+    assert_eq!(Region::parse("HLA:01:02", &index), Ok(Region { name: "HLA:01:02".to_string(), start: None, end: None }));
+    assert_eq!(fetch(&mut file, "HLA:01:02").unwrap(), "ACGTACGT");
+    assert_eq!(fetch(&mut file, "HLA:01:02:3-5").unwrap(), "GTA");
+    assert_eq!(fetch(&mut file, "HLA:01:2-3").unwrap(), "TT");
+    assert!(Region::parse("HLA:01:x", &index).is_err());
+  }
+
   // #[test]
   // fn test_parse_fasta_records() {
   //   let input = ">id descr\natgcatgcgtcgtatcgta\ngcgtcgatctgca\n>id descr\natgcatgcgtcgtatcgta\ngcgtcgatctgca\n";
@@ -0,0 +1,407 @@
+//! # FASTQ
+//!
+//! Reading and writing of FASTQ sequencing reads, including multi-line
+//! records, and decoding of Phred+33 and Phred+64 quality strings.
+
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::io::{self, BufRead};
+use std::str::FromStr;
+
+use crate::alphabet::{InvalidResidue, Validator};
+
+use super::error::{LineReader, ParseError, ReadError};
+
+use super::fasta::{FastaDescription, FastaRecord};
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct FastqRecord {
+  pub id: String,
+  pub description: Option<String>,
+  pub seq: String,
+  /// The encoded quality string, one character per base of `seq`.
+  pub qualities: String,
+}
+
+impl FastqRecord {
+  /// Build a record from a fasta record, giving every base the same quality.
+  ///
+  /// Qualities are encoded as Phred+33.
+  pub fn from_fasta(fasta: FastaRecord, phred: u8) -> FastqRecord {
+    let fd = FastaDescription::read(&fasta.descr_line);
+    let qualities = QualityEncoding::Phred33.encode(&vec![phred; fasta.seq.len()]);
+    FastqRecord {
+      id: fd.identifier.unwrap_or_default(),
+      description: fd.description,
+      seq: fasta.seq,
+      qualities,
+    }
+  }
+
+  /// The text of the header line, without the leading `@`.
+  pub fn descr_line(&self) -> String {
+    FastaRecord::descr_line(Some(&self.id), self.description.as_deref())
+  }
+
+  /// Decode the quality string into Phred scores.
+  pub fn phred_scores(&self, encoding: QualityEncoding) -> Result<Vec<u8>, QualityError> {
+    encoding.decode(&self.qualities)
+  }
+
+  pub fn write<W : io::Write>(&self, out: &mut W) -> Result<(), io::Error> {
+    writeln!(out, "@{}", self.descr_line())?;
+    writeln!(out, "{}", self.seq)?;
+    writeln!(out, "+")?;
+    writeln!(out, "{}", self.qualities)?;
+
+    Ok(())
+  }
+}
+
+impl From<FastqRecord> for FastaRecord {
+  fn from(fq: FastqRecord) -> Self {
+    FastaRecord {
+      descr_line: fq.descr_line(),
+      seq: fq.seq,
+    }
+  }
+}
+
+
+/// The offset used to store Phred scores as printable characters.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum QualityEncoding {
+  /// Sanger and Illumina 1.8+ encoding, with `!` as quality 0.
+  Phred33,
+  /// Illumina 1.3 to 1.7 encoding, with `@` as quality 0.
+  Phred64,
+}
+
+impl QualityEncoding {
+  pub fn offset(self) -> u8 {
+    match self {
+      QualityEncoding::Phred33 => 33,
+      QualityEncoding::Phred64 => 64,
+    }
+  }
+
+  pub fn decode(self, qualities: &str) -> Result<Vec<u8>, QualityError> {
+    let offset = self.offset();
+    qualities.bytes().enumerate().map(|(i, q)| {
+      if q >= offset && q <= b'~' {
+        Ok(q - offset)
+      } else {
+        Err(QualityError { position: i, found: q as char, encoding: self })
+      }
+    }).collect()
+  }
+
+  /// Encode Phred scores, capping them at the largest printable value.
+  pub fn encode(self, scores: &[u8]) -> String {
+    let offset = self.offset();
+    let max = b'~' - offset;
+    scores.iter().map(|s| (offset + (*s).min(max)) as char).collect()
+  }
+}
+
+impl FromStr for QualityEncoding {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "phred33" | "33" => Ok(QualityEncoding::Phred33),
+      "phred64" | "64" => Ok(QualityEncoding::Phred64),
+      e => Err(format!("Unknown quality encoding `{}'. Expected phred33 or phred64", e)),
+    }
+  }
+}
+
+/// A quality character that's out of range for its encoding.
+#[derive(Debug, PartialEq)]
+pub struct QualityError {
+  /// Offset of the character in the quality string, counting from 0.
+  pub position: usize,
+  pub found: char,
+  pub encoding: QualityEncoding,
+}
+
+impl Display for QualityError {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    write!(f, "Quality character `{}' at position {} is not valid {:?}",
+      self.found, self.position, self.encoding)
+  }
+}
+
+impl Error for QualityError {}
+
+
+/// Errors raised while streaming FASTQ records with a [FastqReader]. A record
+/// that isn't laid out as FASTQ, or holds a residue rejected by a [Validator],
+/// is a [ReadError::Parse], and reading can carry on with the next record.
+pub type FastqError = ReadError;
+
+/// Streaming FASTQ reader, yielding one record at a time.
+///
+/// Sequence and quality strings may be wrapped over several lines. The end of
+/// the quality block is found by length, so quality lines starting with `@` or
+/// `+` are handled correctly.
+pub struct FastqReader<R> {
+  lines: LineReader<R>,
+  /// True if the current line has been read but not yet used.
+  held: bool,
+  record: usize,
+  validator: Option<Validator>,
+  warnings: Vec<InvalidResidue>,
+  done: bool,
+}
+
+impl <R : BufRead> FastqReader<R> {
+  pub fn new(reader: R) -> FastqReader<R> {
+    FastqReader {
+      lines: LineReader::new(reader),
+      held: false,
+      record: 0,
+      validator: None,
+      warnings: Vec::new(),
+      done: false,
+    }
+  }
+
+  pub fn with_validator(self, validator: Option<Validator>) -> FastqReader<R> {
+    FastqReader { validator, ..self }
+  }
+
+  /// Take the residues kept under [crate::alphabet::Policy::Warn] since this
+  /// was last called.
+  pub fn take_warnings(&mut self) -> Vec<InvalidResidue> {
+    std::mem::take(&mut self.warnings)
+  }
+
+  /// Read the next line, stripping its line ending, returning false at end
+  /// of input.
+  fn read_line(&mut self) -> Result<bool, io::Error> {
+    if self.held {
+      self.held = false;
+      return Ok(true);
+    }
+    if !self.lines.read_line()? { return Ok(false) }
+    let line = self.lines.line_mut();
+    while let Some(b'\n') | Some(b'\r') = line.last() {
+      line.pop();
+    }
+    Ok(true)
+  }
+
+  fn format_error<T>(&self, message: &str) -> Result<T, FastqError> {
+    let e = ParseError::new(self.lines.line_no(), message);
+    Err(FastqError::Parse(if self.record > 0 { e.with_record(self.record) } else { e }))
+  }
+
+  /// Locate the first byte of the current line outside of `allowed`.
+  fn check_bytes<F : Fn(&u8) -> bool>(&self, allowed: F, what: &str) -> Option<ParseError> {
+    let c = self.lines.line().iter().position(|b| !allowed(b))?;
+    let e = ParseError::new(self.lines.line_no(), format!("byte `{}' in the {} is not valid",
+        self.lines.line()[c].escape_ascii(), what));
+    Some(e.with_column(c + 1).with_record(self.record))
+  }
+
+  fn read_record(&mut self) -> Result<Option<FastqRecord>, FastqError> {
+    // skip blank lines between records
+    loop {
+      if !self.read_line()? { return Ok(None) }
+      if !self.lines.line().iter().all(u8::is_ascii_whitespace) { break }
+    }
+
+    if self.lines.line().first() != Some(&b'@') {
+      let e = self.format_error("expected a `@` header line");
+      // skip to what looks like the next header
+      while self.read_line()? {
+        if self.lines.line().first() == Some(&b'@') {
+          self.held = true;
+          break;
+        }
+      }
+      return e;
+    }
+    self.record += 1;
+    let fd = FastaDescription::read(&String::from_utf8_lossy(&self.lines.line()[1..]));
+
+    // on an invalid residue, read the rest of the record so the next one can be read.
+    // Bytes are checked before conversion to a string, which would change the
+    // length of the sequence or qualities on anything but ASCII.
+    let mut invalid = None;
+    let mut seq = Vec::new();
+    loop {
+      if !self.read_line()? {
+        return self.format_error("end of input before the `+` separator line");
+      }
+      if self.lines.line().first() == Some(&b'+') { break }
+      if invalid.is_none() {
+        invalid = self.check_bytes(u8::is_ascii, "sequence");
+      }
+      if let (Some(v), None) = (&self.validator, &invalid) {
+        let id = fd.identifier.as_deref().unwrap_or("");
+        match v.check_line(id, self.lines.line_no(), self.lines.line_mut()) {
+          Ok(w) => self.warnings.extend(w),
+          Err(e) => invalid = Some(ParseError::invalid_residue(&e, self.record)),
+        }
+      }
+      seq.extend(self.lines.line().iter().filter(|c| !c.is_ascii_whitespace()));
+    }
+
+    let mut qualities = Vec::with_capacity(seq.len());
+    while qualities.len() < seq.len() {
+      if !self.read_line()? {
+        return self.format_error("end of input before the quality string was complete");
+      }
+      if invalid.is_none() {
+        invalid = self.check_bytes(|b| (b'!'..=b'~').contains(b), "quality string");
+      }
+      qualities.extend_from_slice(self.lines.line());
+    }
+    if qualities.len() != seq.len() {
+      return self.format_error(&format!(
+        "quality string has length {} but the sequence has length {}",
+        qualities.len(), seq.len()));
+    }
+    if let Some(e) = invalid {
+      return Err(FastqError::Parse(e));
+    }
+
+    Ok(Some(FastqRecord {
+      id: fd.identifier.unwrap_or_default(),
+      description: fd.description,
+      seq: String::from_utf8_lossy(&seq).into_owned(),
+      qualities: String::from_utf8_lossy(&qualities).into_owned(),
+    }))
+  }
+}
+
+impl <R : BufRead> Iterator for FastqReader<R> {
+  type Item = Result<FastqRecord, FastqError>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    if self.done { return None }
+    match self.read_record() {
+      Ok(Some(r)) => Some(Ok(r)),
+      Ok(None) => { self.done = true; None },
+      Err(e @ FastqError::Parse(_)) => Some(Err(e)),
+      Err(e) => { self.done = true; Some(Err(e)) },
+    }
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+
+  fn read(id: &str, description: Option<&str>, seq: &str, qualities: &str) -> FastqRecord {
+    FastqRecord {
+      id: id.to_string(),
+      description: description.map(String::from),
+      seq: seq.to_string(),
+      qualities: qualities.to_string(),
+    }
+  }
+
+  #[test]
+  fn test_fastq_reader_records() {
+    let input: &[u8] = b"@r1 first read\nACGT\n+\nII#!\n@r2\nGG\n+r2\n@+\n";
+    let records: Vec<FastqRecord> = FastqReader::new(input).map(Result::unwrap).collect();
+    assert_eq!(records, vec![
+      read("r1", Some("first read"), "ACGT", "II#!"),
+      read("r2", None, "GG", "@+"),
+    ]);
+  }
+
+  #[test]
+  fn test_fastq_reader_multi_line() {
+    let input: &[u8] = b"@r1\r\nACGT\r\nAC\r\n+\r\n@@@@\r\n@I\r\n\r\n@r2\nA\n+\nI";
+    let records: Vec<FastqRecord> = FastqReader::new(input).map(Result::unwrap).collect();
+    assert_eq!(records, vec![
+      read("r1", None, "ACGTAC", "@@@@@I"),
+      read("r2", None, "A", "I"),
+    ]);
+  }
+
+  #[test]
+  fn test_fastq_reader_truncated() {
+    let input: &[u8] = b"@r1\nACGT\n+\nII\n";
+    match FastqReader::new(input).next() {
+      Some(Err(FastqError::Parse(e))) => assert_eq!((e.line, e.record), (4, Some(1))),
+      r => panic!("Expected a format error but got {:?}", r)
+    }
+  }
+
+  #[test]
+  fn test_fastq_write_round_trip() {
+    let rec = read("r1", Some("first read"), "ACGT", "II#!");
+    let mut out = Vec::new();
+    rec.write(&mut out).unwrap();
+    assert_eq!(out, b"@r1 first read\nACGT\n+\nII#!\n");
+
+    let records: Vec<FastqRecord> = FastqReader::new(&out[..]).map(Result::unwrap).collect();
+    assert_eq!(records, vec![rec]);
+  }
+
+  #[test]
+  fn test_quality_encodings() {
+    assert_eq!(QualityEncoding::Phred33.decode("!+5I"), Ok(vec![0, 10, 20, 40]));
+    assert_eq!(QualityEncoding::Phred64.decode("@JTh"), Ok(vec![0, 10, 20, 40]));
+    assert_eq!(QualityEncoding::Phred33.encode(&[0, 10, 20, 40]), "!+5I");
+    assert_eq!(QualityEncoding::Phred64.encode(&[0, 10, 20, 40]), "@JTh");
+    assert_eq!(
+      QualityEncoding::Phred64.decode("@5"),
+      Err(QualityError { position: 1, found: '5', encoding: QualityEncoding::Phred64 }));
+  }
+
+  #[test]
+  fn test_fasta_conversions() {
+    let fa = FastaRecord { descr_line: "r1 some read".to_string(), seq: "ACG".to_string() };
+    let fq = FastqRecord::from_fasta(fa, 30);
+    assert_eq!(fq, read("r1", Some("some read"), "ACG", "???"));
+
+    let fa: FastaRecord = fq.into();
+    assert_eq!(fa, FastaRecord { descr_line: "r1 some read".to_string(), seq: "ACG".to_string() });
+  }
+
+  #[test]
+  fn test_fastq_reader_validation() {
+    use crate::alphabet::{Alphabet, Policy};
+
+    let input: &[u8] = b"@r1\nACGT\n+\nIIII\n@r2\nAC\nGX\n+\nIIII\n";
+    let strict = Some(Validator::new(Alphabet::Dna, Policy::Strict));
+    let mut reader = FastqReader::new(input).with_validator(strict);
+    assert!(reader.next().unwrap().is_ok());
+    match reader.next() {
+      Some(Err(FastqError::Parse(e))) => {
+        assert_eq!((e.line, e.column, e.record), (7, Some(2), Some(2)));
+        assert!(e.message.contains("`X' in `r2'"));
+      }
+      r => panic!("Expected an invalid residue but got {:?}", r),
+    }
+
+    let replace = Some(Validator::new(Alphabet::Dna, Policy::Replace(b'N')));
+    let records: Vec<FastqRecord> = FastqReader::new(input).with_validator(replace).map(Result::unwrap).collect();
+    assert_eq!(records[1].seq, "ACGN");
+  }
+
+  #[test]
+  fn test_fastq_reader_invalid_bytes() {
+    let input: &[u8] = b"@r1\nACGT\n+\nII\xffI\n@r2\nA\xc3\xa9T\n+\nIIII\n@r3\nAC\n+\nI I\n@r4\nAC\n+\nII\n";
+    let results: Vec<Result<FastqRecord, FastqError>> = FastqReader::new(input).collect();
+    assert_eq!(results.len(), 4);
+    let located = |r: &Result<FastqRecord, FastqError>| match r {
+      Err(FastqError::Parse(e)) => (e.line, e.column, e.record, e.message.clone()),
+      r => panic!("Expected an invalid byte but got {:?}", r),
+    };
+    assert_eq!(located(&results[0]),
+      (4, Some(3), Some(1), "byte `\\xff' in the quality string is not valid".to_string()));
+    assert_eq!(located(&results[1]),
+      (6, Some(2), Some(2), "byte `\\xc3' in the sequence is not valid".to_string()));
+    assert_eq!(located(&results[2]).0, 12);
+    assert_eq!(results[3].as_ref().unwrap(), &read("r4", None, "AC", "II"));
+  }
+}
@@ -12,20 +12,22 @@ use nom::{
   bytes::complete::{
     tag,
     take_while_m_n,
-    take_while,
     take_while1,
   },
   character::{
     is_alphanumeric,
   },
   combinator::{
+    all_consuming,
     cut,
     map,
     opt,
     verify,
     },
   error::{
+    ErrorKind,
     ParseError,
+    VerboseError,
   },
   multi::{
     // many1,
@@ -36,26 +38,187 @@ use nom::{
   },
 };
 
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+use super::error;
 use super::parser::Nommed;
 
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
 pub struct FeatureTable {
-  features: Vec<FeatureRecord>
+  pub features: Vec<FeatureRecord>
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub struct FeatureRecord {
+  pub key: String,
+  pub location: LocOp,
+  pub qualifiers: Vec<Qualifier>
+}
+
+impl FeatureRecord {
+  /// The value of the first qualifier called `name`.
+  pub fn qualifier(&self, name: &str) -> Option<&QualifierValue> {
+    self.qualifiers.iter()
+      .find(|q| q.name.as_str() == name)
+      .and_then(|q| q.value.as_ref())
+  }
+}
+
+/// Width of the feature key column, after the leading line code.
+const KEY_COLUMNS: usize = 16;
+
+/// A feature being gathered from its lines.
+struct PendingFeature {
+  line: usize,
   key: String,
-  location: LocOp,
-  qualifiers: Vec<Qualifier>
+  location: String,
+  /// The line each qualifier starts on, and its text with any continuation
+  /// lines joined on.
+  qualifiers: Vec<(usize, String)>,
 }
 
-// impl <'a, E : ParseError<&'a str>> Nommed<&'a str, E> for FeatureRecord {
-//   fn nom(input: &'a str) -> IResult<&'a str, FeatureRecord, E> {
+impl PendingFeature {
+  fn parse(self) -> Result<FeatureRecord, error::ParseError> {
+    let location = match all_consuming(<LocOp as Nommed<&str, VerboseError<&str>>>::nom)(&self.location) {
+      Ok((_, loc)) => loc,
+      Err(_) => return Err(error::ParseError::new(self.line,
+        format!("cannot parse the location `{}' of {} feature", self.location, self.key))),
+    };
+    let qualifiers = self.qualifiers.iter()
+      .map(|(line, text)| match all_consuming(<Qualifier as Nommed<&str, VerboseError<&str>>>::nom)(text) {
+        Ok((_, q)) => Ok(q),
+        Err(_) => Err(error::ParseError::new(*line, format!("cannot parse the qualifier `{}'", text))),
+      })
+      .collect::<Result<Vec<_>, _>>()?;
+    Ok(FeatureRecord { key: self.key, location, qualifiers })
+  }
+}
 
-//   }
-// }
+impl FeatureTable {
+  /// Parse feature table lines, each paired with its line number and with
+  /// the leading line code (`FT   ` in EMBL, or five spaces in GenBank)
+  /// removed, so that keys start in the first column and locations and
+  /// qualifiers in the seventeenth.
+  ///
+  /// Continuation lines of quoted qualifier values are joined with a space,
+  /// except for `/translation`, which is joined without.
+  pub fn parse_lines<'a, I>(lines: I) -> Result<FeatureTable, error::ParseError>
+  where I: IntoIterator<Item = (usize, &'a str)>
+  {
+    let mut features = Vec::new();
+    let mut pending: Option<PendingFeature> = None;
+    for (line, text) in lines {
+      let text = text.trim_end();
+      if text.trim().is_empty() { continue }
+
+      if !text.starts_with(' ') {
+        features.extend(pending.take().map(PendingFeature::parse).transpose()?);
+        let split = text.char_indices().nth(KEY_COLUMNS).map_or(text.len(), |(i, _)| i);
+        let (key, location) = text.split_at(split);
+        pending = Some(PendingFeature {
+          line,
+          key: key.trim().to_string(),
+          location: location.trim().to_string(),
+          qualifiers: Vec::new() });
+        continue;
+      }
+
+      let f = pending.as_mut().ok_or_else(|| error::ParseError::new(line,
+        "feature table continuation line without a feature"))?;
+      let value = text.trim();
+      let open_quote = f.qualifiers.last().is_some_and(|(_, q)| q.matches('"').count() % 2 == 1);
+      match f.qualifiers.last_mut() {
+        Some((_, q)) if open_quote => {
+          if !q.starts_with("/translation=") { q.push(' ') }
+          q.push_str(value);
+        }
+        _ if value.starts_with('/') => f.qualifiers.push((line, value.to_string())),
+        None => f.location.push_str(value),
+        // unquoted values have no spaces, so their lines join without one
+        Some((_, q)) => q.push_str(value),
+      }
+    }
+    features.extend(pending.map(PendingFeature::parse).transpose()?);
+    Ok(FeatureTable { features })
+  }
+
+  /// Write the features in the layout read by [FeatureTable::parse_lines],
+  /// starting each line with `prefix`, and wrapping at 80 columns.
+  ///
+  /// Locations and unquoted values are wrapped after commas, and quoted
+  /// qualifier values at spaces, running past 80 columns where there's no
+  /// break. Only `/translation` and other values that can't hold either are
+  /// cut wherever they reach the end of a line, as their lines are read back
+  /// without anything between them.
+  pub fn write_lines<W : fmt::Write>(&self, prefix: &str, out: &mut W) -> fmt::Result {
+    let indent = format!("{}{:width$}", prefix, "", width = KEY_COLUMNS);
+    let width = LINE_WIDTH.saturating_sub(indent.len());
+    for f in &self.features {
+      for (i, line) in wrap(&f.location.to_string(), width, &[',']).iter().enumerate() {
+        match i {
+          0 => writeln!(out, "{}{:<width$}{}", prefix, f.key, line, width = KEY_COLUMNS)?,
+          _ => writeln!(out, "{}{}", indent, line)?,
+        }
+      }
+      for q in &f.qualifiers {
+        let breaks: &[char] = match &q.value {
+          Some(QualifierValue::QuotedText(_)) if q.name.as_str() != "translation" => &[' '],
+          Some(QualifierValue::Unquoted(_)) => &[','],
+          _ => &[],
+        };
+        for line in wrap(&q.to_string(), width, breaks) {
+          writeln!(out, "{}{}", indent, line)?;
+        }
+      }
+    }
+    Ok(())
+  }
+}
+
+/// The widest line in flat-file layouts.
+pub const LINE_WIDTH: usize = 80;
+
+/// Split `text` into lines of at most `width` characters. Lines are broken
+/// after the last of the `breaks` characters that fits, and a space at a break
+/// is dropped. Where no break fits, the line runs on to the next one, so that
+/// reading the lines back only has to undo the breaks. With no `breaks` at
+/// all, lines are cut at exactly `width` characters.
+pub fn wrap(text: &str, width: usize, breaks: &[char]) -> Vec<String> {
+  let width = width.max(1);
+  let mut lines = Vec::new();
+  let mut rest = text;
+  while let Some((limit, _)) = rest.char_indices().nth(width) {
+    let mut fits = None;
+    let mut over = None;
+    for b in break_points(rest, breaks) {
+      // a space can fall just past the end of the line, as it is dropped
+      if b.0 <= limit { fits = Some(b) } else { over = Some(b); break }
+    }
+    let (end, next) = match fits.or(over) {
+      Some(b) => b,
+      None if breaks.is_empty() => (limit, limit),
+      None => break,
+    };
+    lines.push(rest[..end].to_string());
+    rest = &rest[next..];
+  }
+  lines.push(rest.to_string());
+  lines
+}
+
+/// Where `text` can be broken at one of `breaks`, as the end of one line and
+/// the start of the next. Spaces are only broken at between two other
+/// characters, as any more would be lost when the lines are trimmed.
+fn break_points<'a>(text: &'a str, breaks: &'a [char]) -> impl Iterator<Item = (usize, usize)> + 'a {
+  let previous = std::iter::once(None).chain(text.chars().map(Some));
+  let next = text.chars().skip(1).map(Some).chain(std::iter::once(None));
+  text.char_indices().zip(previous).zip(next)
+    .filter(move |&(((i, c), previous), next)| i > 0 && breaks.contains(&c)
+      && (c != ' ' || (previous != Some(' ') && next.is_some_and(|n| n != ' '))))
+    .map(|(((i, c), _), _)| if c == ' ' { (i, i + 1) } else { (i + 1, i + 1) })
+}
 
 
 
@@ -69,10 +232,31 @@ pub struct FeatureRecord {
 ///   * Hyphen (-)
 ///   * Single quote (')
 ///   * Asterisk (*)
+///
 /// The maximum length is 20 characters.
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub struct FtString(String);
 
+impl FtString {
+  pub fn as_str(&self) -> &str { &self.0 }
+}
+
+impl FromStr for FtString {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    all_consuming(<FtString as Nommed<&str, VerboseError<&str>>>::nom)(s)
+      .map(|(_, n)| n)
+      .map_err(|_| format!("`{}' is not a valid qualifier name", s))
+  }
+}
+
+impl Display for FtString {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    write!(f, "{}", self.0)
+  }
+}
+
 // litle utility for ranges.
 //
 // Note: couldn't use 'a'..='b' because this is an iterator, so doesn't
@@ -119,10 +303,10 @@ impl <'a, E : ParseError<&'a str>> Nommed<&'a str, E> for FtString {
 }
 
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub struct Qualifier {
-  name: FtString,
-  value: Option<QualifierValue>
+  pub name: FtString,
+  pub value: Option<QualifierValue>
 }
 
 impl <'a, E : ParseError<&'a str>> Nommed<&'a str, E> for Qualifier {
@@ -138,26 +322,86 @@ fn nom(input: &'a str) -> IResult<&'a str, Qualifier, E> {
 }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub enum QualifierValue {
   QuotedText(String),
   VocabularyTerm(FtString),
   ReferenceNumber(u32),
+  /// Any other unquoted value, such as `/transl_table=11` or
+  /// `/anticodon=(pos:34..36,aa:Phe,seq:aaa)`.
+  Unquoted(String),
+}
+
+impl Display for Qualifier {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    write!(f, "/{}", self.name)?;
+    match &self.value {
+      Some(v) => write!(f, "={}", v),
+      None => Ok(()),
+    }
+  }
+}
+
+impl QualifierValue {
+  /// The value as text, without any quotes or brackets.
+  pub fn text(&self) -> String {
+    match self {
+      QualifierValue::QuotedText(t) | QualifierValue::Unquoted(t) => t.clone(),
+      QualifierValue::VocabularyTerm(t) => t.0.clone(),
+      QualifierValue::ReferenceNumber(n) => n.to_string(),
+    }
+  }
+}
+
+/// Written as it appears in a qualifier, with quoted text in quotes and any
+/// quotes within it doubled.
+impl Display for QualifierValue {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    match self {
+      QualifierValue::QuotedText(t) => write!(f, "\"{}\"", t.replace('"', "\"\"")),
+      QualifierValue::VocabularyTerm(t) => write!(f, "{}", t),
+      QualifierValue::ReferenceNumber(n) => write!(f, "[{}]", n),
+      QualifierValue::Unquoted(t) => write!(f, "{}", t),
+    }
+  }
+}
+
+/// Text between double quotes, where a doubled quote stands for one quote.
+fn quoted_text<'a, E : ParseError<&'a str>>(input: &'a str) -> IResult<&'a str, String, E> {
+  let (mut rest, _) = tag("\"")(input)?;
+  let mut text = String::new();
+  loop {
+    match rest.find('"') {
+      None => return Err(nom::Err::Error(E::from_error_kind(rest, ErrorKind::Tag))),
+      Some(i) => {
+        text.push_str(&rest[..i]);
+        rest = &rest[i + 1..];
+        if rest.starts_with('"') {
+          text.push('"');
+          rest = &rest[1..];
+        } else {
+          return Ok((rest, text))
+        }
+      }
+    }
+  }
 }
 
 impl <'a, E : ParseError<&'a str>> Nommed<&'a str, E> for QualifierValue{
 
 fn nom(input: &'a str) -> IResult<&'a str, QualifierValue, E> {
-  let parse_quoted_text =
-    map(
-      tuple((tag("\""), take_while(|c| c != '"'), tag("\""))),
-      |(_, v, _): (&str, &str, &str)| QualifierValue::QuotedText(v.to_string()));
+  let parse_quoted_text = map(quoted_text, QualifierValue::QuotedText);
 
   let parse_vocabulary_term =
     map(
-      FtString::nom,
+      all_consuming(FtString::nom),
       QualifierValue::VocabularyTerm);
 
+  let parse_unquoted =
+    map(
+      take_while1(|c: char| !c.is_whitespace()),
+      |v: &str| QualifierValue::Unquoted(v.to_string()));
+
   let parse_reference_number =
     map(
       tuple((tag("["), u32::nom, tag("]"))),
@@ -165,8 +409,9 @@ fn nom(input: &'a str) -> IResult<&'a str, QualifierValue, E> {
 
   alt((
     parse_quoted_text,
+    parse_reference_number,
     parse_vocabulary_term,
-    parse_reference_number
+    parse_unquoted,
   ))(input)
 }
 }
@@ -181,9 +426,15 @@ fn nom(input: &'a str) -> IResult<&'a str, QualifierValue, E> {
 
 
 /// A point within a sequence, representing a specific nucleotide. Counts from 1.
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub struct Point(u32);
 
+impl Display for Point {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    write!(f, "{}", self.0)
+  }
+}
+
 impl <'a, E : ParseError<&'a str>> Nommed<&'a str, E> for Point {
   fn nom(input: &'a str) -> IResult<&'a str, Point, E> {
     map(u32::nom, Point)(input)
@@ -196,9 +447,15 @@ impl <'a, E : ParseError<&'a str>> Nommed<&'a str, E> for Point {
 /// For example, 122^123. The locations must be consecutive.
 ///
 /// For example, 100^1 for a circular sequence of length 100.
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub struct Between(u32, u32);
 
+impl Display for Between {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    write!(f, "{}^{}", self.0, self.1)
+  }
+}
+
 impl <'a, E : ParseError<&'a str>> Nommed<&'a str, E> for Between {
 fn nom(input: &'a str) -> IResult<&'a str, Between, E> {
   map(
@@ -213,12 +470,21 @@ fn nom(input: &'a str) -> IResult<&'a str, Between, E> {
 }
 
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub enum Position {
   Point(Point),
   Between(Between)
 }
 
+impl Display for Position {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    match self {
+      Position::Point(p) => write!(f, "{}", p),
+      Position::Between(b) => write!(f, "{}", b),
+    }
+  }
+}
+
 impl <'a, E : ParseError<&'a str>> Nommed<&'a str, E> for Position {
 fn nom(input: &'a str) -> IResult<&'a str, Position, E> {
   alt((
@@ -229,7 +495,7 @@ fn nom(input: &'a str) -> IResult<&'a str, Position, E> {
 }
 
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub enum Local {
   Point(Point),
   Between(Between),
@@ -247,6 +513,19 @@ impl Local {
   }
 }
 
+impl Display for Local {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    match self {
+      Local::Point(p) => write!(f, "{}", p),
+      Local::Between(b) => write!(f, "{}", b),
+      Local::Within { from, to } => write!(f, "{}.{}", from, to),
+      Local::Span { from, to, before_from, after_to } => write!(f, "{}{}..{}{}",
+        if *before_from { "<" } else { "" }, from,
+        if *after_to { ">" } else { "" }, to),
+    }
+  }
+}
+
 impl <'a, E : ParseError<&'a str>> Nommed<&'a str, E> for Local {
   fn nom(input: &'a str) -> IResult<&'a str, Local, E> {
     let parse_within = map(
@@ -273,12 +552,21 @@ impl <'a, E : ParseError<&'a str>> Nommed<&'a str, E> for Local {
 }
 
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub enum Loc {
   Remote { within: String, at: Local },
   Local(Local)
 }
 
+impl Display for Loc {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    match self {
+      Loc::Remote { within, at } => write!(f, "{}:{}", within, at),
+      Loc::Local(l) => write!(f, "{}", l),
+    }
+  }
+}
+
 impl <'a, E : ParseError<&'a str>> Nommed<&'a str, E> for Loc {
 fn nom(input: &'a str) -> IResult<&'a str, Loc, E> {
   let parse_accession = take_while1(|c| {
@@ -298,7 +586,7 @@ fn nom(input: &'a str) -> IResult<&'a str, Loc, E> {
 
 
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub enum LocOp {
   Loc(Loc),
   Complement(Box<LocOp>),
@@ -306,6 +594,25 @@ pub enum LocOp {
   Order(Vec<LocOp>)
 }
 
+impl Display for LocOp {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    let list = |f: &mut Formatter<'_>, op: &str, locs: &[LocOp]| {
+      write!(f, "{}(", op)?;
+      for (i, l) in locs.iter().enumerate() {
+        if i > 0 { write!(f, ",")? }
+        write!(f, "{}", l)?;
+      }
+      write!(f, ")")
+    };
+    match self {
+      LocOp::Loc(l) => write!(f, "{}", l),
+      LocOp::Complement(l) => write!(f, "complement({})", l),
+      LocOp::Join(ls) => list(f, "join", ls),
+      LocOp::Order(ls) => list(f, "order", ls),
+    }
+  }
+}
+
 impl <'a, E : ParseError<&'a str>> Nommed<&'a str, E> for LocOp {
 fn nom(input: &'a str) -> IResult<&'a str, LocOp, E> {
 
@@ -357,7 +664,7 @@ mod tests {
     VerboseError,
   };
 
-  fn assert_nom_to_expected<'a, T>() -> impl Fn(&'a str, T) -> ()
+  fn assert_nom_to_expected<'a, T>() -> impl Fn(&'a str, T)
     where
       T: Nommed<&'a str, VerboseError<&'a str>> + std::fmt::Debug + PartialEq
   {
@@ -515,4 +822,56 @@ mod tests {
       ]));
 
   }
-}
\ No newline at end of file
+
+  #[test]
+  fn test_display_round_trips() {
+    for loc in &["467", "340..565", "<345..500", "<1..888", "1..>888", "102.110", "123^124",
+        "join(12..78,134..202)", "complement(join(2691..4571,4918..5163))",
+        "order(complement(1..4),J00194.1:100..202)"] {
+      let (_, parsed) = all_consuming(<LocOp as Nommed<&str, VerboseError<&str>>>::nom)(loc).unwrap();
+      assert_eq!(&parsed.to_string(), loc);
+    }
+    for q in &["/pseudo", "/citation=[1]", "/rpt_type=DISPERSED", "/transl_table=11",
+        "/note=\"a \"\"quoted\"\" word\""] {
+      let (_, parsed) = all_consuming(<Qualifier as Nommed<&str, VerboseError<&str>>>::nom)(q).unwrap();
+      assert_eq!(&parsed.to_string(), q);
+    }
+  }
+
+  #[test]
+  fn test_wrap() {
+    assert_eq!(wrap("one two three", 7, &[' ']), vec!["one two", "three"]);
+    assert_eq!(wrap("one two three", 8, &[' ']), vec!["one two", "three"]);
+    assert_eq!(wrap("join(1..2,3..4)", 12, &[',']), vec!["join(1..2,", "3..4)"]);
+    assert_eq!(wrap("MDFIVAIFAL", 4, &[]), vec!["MDFI", "VAIF", "AL"]);
+    assert_eq!(wrap("short", 80, &[' ']), vec!["short"]);
+    assert_eq!(wrap("see https://example.org/a/long/path here", 8, &[' ']), vec!["see", "https://example.org/a/long/path", "here"]);
+    assert_eq!(wrap("two  spaces", 5, &[' ']), vec!["two  spaces"]);
+  }
+
+  #[test]
+  fn test_write_lines() {
+    fn lines(t: &str) -> Vec<(usize, &str)> {
+      t.lines().enumerate().map(|(i, l)| (i + 1, l)).collect()
+    }
+    let text = "\
+CDS             join(1000..2000,3000..4000,5000..6000,7000..8000,9000..10000,
+                11000..12000)
+                /note=\"a long note that has to be wrapped onto a second line of
+                text\"
+                /translation=\"MDFIVAIFALFVISSFTITSTNAVEASTLLDIGNLSRSSFPRGFIFGAGS
+                SAYQ\"
+                /codon_start=1
+                /note=\"see
+                https://www.ebi.ac.uk/ena/browser/api/embl/X56734?lineLimit=1000&download=true
+                for the entry\"
+";
+    let table = FeatureTable::parse_lines(lines(text)).unwrap();
+    let url = "see https://www.ebi.ac.uk/ena/browser/api/embl/X56734?lineLimit=1000&download=true for the entry";
+    assert_eq!(table.features[0].qualifiers.last().unwrap().value, Some(QualifierValue::QuotedText(url.to_string())));
+    let mut written = String::new();
+    table.write_lines("", &mut written).unwrap();
+    assert_eq!(written, text);
+    assert_eq!(FeatureTable::parse_lines(lines(&written)).unwrap(), table);
+  }
+}
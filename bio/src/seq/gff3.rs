@@ -5,18 +5,23 @@
 // todo: This implementation doesn't implement any of the string excaping rules.
 // todo: If and when it's extended to support that, consider using a wrapper
 //   around string to enforce escaping
-// todo: Handle comments
 
+use std::collections::HashMap;
 use std::fmt::Formatter;
 use std::fmt::Display;
-use std::collections::HashMap;
+use std::io::BufRead;
+use std::ops::Range;
 use std::str::FromStr;
 
+use crate::interval::IntervalTree;
+
+use super::error::{LineReader, ParseError, ReadError};
+
 // Fields use `String` rather than `&str` so that a record can live independently
 // of a parse.
 //
 // Howeer, it may make sense to refactor this.
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct GffRecord {
   pub seq_id: String,
   pub source: String,
@@ -76,9 +81,24 @@ impl FromStr for GffRecord {
   }
 }
 
+impl GffRecord {
+  /// The extent of the feature, counting from 0 and half-open.
+  pub fn range(&self) -> Range<u64> {
+    self.start.at().saturating_sub(1)..self.end.at()
+  }
+}
+
+impl Display for GffRecord {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+      self.seq_id, self.source, self.feature_type, self.start, self.end,
+      self.score, self.strand, self.phase, self.attributes)
+  }
+}
+
 
 // Index counted from 1 rather than 0
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
 pub struct OneBased(u64);
 impl OneBased {
   pub fn new(at: u64) -> OneBased { OneBased(at) }
@@ -93,8 +113,18 @@ impl FromStr for OneBased {
   }
 }
 
-#[derive(Debug)]
+impl Display for OneBased {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}", self.0)
+  }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
 pub struct Score(Option<f64>);
+impl Score {
+  pub fn new(score: Option<f64>) -> Score { Score(score) }
+  pub fn score(&self) -> Option<f64> { self.0 }
+}
 
 impl FromStr for Score {
   type Err = GffParseError;
@@ -108,9 +138,18 @@ impl FromStr for Score {
   }
 }
 
+impl Display for Score {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    match self.0 {
+      Some(s) => write!(f, "{}", s),
+      None => write!(f, "."),
+    }
+  }
+}
 
 
-#[derive(Debug)]
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum Strand {
   Positive,
   Negative,
@@ -132,9 +171,24 @@ impl FromStr for Strand {
   }
 }
 
+impl Display for Strand {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}", match self {
+      Strand::Positive => "+",
+      Strand::Negative => "-",
+      Strand::NoStrand => ".",
+      Strand::Unknown => "?",
+    })
+  }
+}
+
 // 0, 1, 2
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub struct Phase(Option<u8>);
+impl Phase {
+  pub fn new(phase: Option<u8>) -> Phase { Phase(phase) }
+  pub fn phase(&self) -> Option<u8> { self.0 }
+}
 
 impl FromStr for Phase {
   type Err = GffParseError;
@@ -148,16 +202,40 @@ impl FromStr for Phase {
   }
 }
 
+impl Display for Phase {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    match self.0 {
+      Some(p) => write!(f, "{}", p),
+      None => write!(f, "."),
+    }
+  }
+}
 
 
-#[derive(Debug)]
-pub struct Attributes(HashMap<String, String>);
+
+// Tag-value pairs, in the order they were read so that records are written
+// back out unchanged.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Attributes(Vec<(String, String)>);
+impl Attributes {
+  pub fn get(&self, tag: &str) -> Option<&str> {
+    self.0.iter().find(|(t, _)| t == tag).map(|(_, v)| v.as_str())
+  }
+
+  /// Set the value of `tag`, replacing any it already has.
+  pub fn set(&mut self, tag: &str, value: &str) {
+    match self.0.iter_mut().find(|(t, _)| t == tag) {
+      Some((_, v)) => *v = value.to_string(),
+      None => self.0.push((tag.to_string(), value.to_string())),
+    }
+  }
+}
 
 impl FromStr for Attributes {
   type Err = GffParseError;
 
   fn from_str(s: &str) -> Result<Self, Self::Err> {
-    let tvs: HashMap<String, String> = s.split(';').flat_map(|p| {
+    let tvs: Vec<(String, String)> = s.split(';').flat_map(|p| {
       let mut tv = p.split('=');
       match (tv.next(), tv.next()) {
         (Some(t), Some(v)) => Ok((t.to_string(), v.to_string())),
@@ -171,6 +249,19 @@ impl FromStr for Attributes {
   }
 }
 
+impl Display for Attributes {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    if self.0.is_empty() {
+      return write!(f, ".")
+    }
+    for (i, (t, v)) in self.0.iter().enumerate() {
+      if i > 0 { write!(f, ";")? }
+      write!(f, "{}={}", t, v)?;
+    }
+    Ok(())
+  }
+}
+
 
 
 #[derive(Debug)]
@@ -183,4 +274,169 @@ impl Display for GffParseError {
   fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
     write!(f, "Unable to parse GFF3 record because: {}", self.0)
   }
-}
\ No newline at end of file
+}
+
+
+/// Errors raised while streaming GFF3 records with a [GffReader]. A line that
+/// isn't a GFF3 record is a [ReadError::Parse], and reading can carry on with
+/// the next line.
+pub type GffError = ReadError;
+
+/// Streaming GFF3 reader, yielding one record per feature line.
+///
+/// Comments, directives and blank lines are skipped, and reading stops at any
+/// `##FASTA` section.
+pub struct GffReader<R> {
+  lines: LineReader<R>,
+  record: usize,
+  done: bool,
+}
+
+impl <R : BufRead> GffReader<R> {
+  pub fn new(reader: R) -> GffReader<R> {
+    GffReader { lines: LineReader::new(reader), record: 0, done: false }
+  }
+}
+
+impl <R : BufRead> Iterator for GffReader<R> {
+  type Item = Result<GffRecord, GffError>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    while !self.done {
+      match self.lines.read_line() {
+        Ok(false) => self.done = true,
+        Ok(true) => {
+          let line = String::from_utf8_lossy(self.lines.line());
+          if line.starts_with("##FASTA") {
+            self.done = true;
+          } else if !line.starts_with('#') && !line.trim().is_empty() {
+            self.record += 1;
+            return Some(GffRecord::from_str(&line).map_err(|e| GffError::Parse(
+              ParseError::new(self.lines.line_no(), e.to_string()).with_record(self.record))))
+          }
+        }
+        Err(e) => {
+          self.done = true;
+          return Some(Err(GffError::Io(e)))
+        }
+      }
+    }
+    None
+  }
+}
+
+
+/// GFF3 records grouped by seqid, with an interval tree over each sequence
+/// for finding the features overlapping a region.
+#[derive(Debug, Clone, Default)]
+pub struct GffIndex {
+  /// Seqids in the order they were first seen.
+  seq_ids: Vec<String>,
+  trees: HashMap<String, IntervalTree<GffRecord>>,
+}
+
+impl GffIndex {
+  pub fn new<I : IntoIterator<Item = GffRecord>>(records: I) -> GffIndex {
+    let mut seq_ids = Vec::new();
+    let mut grouped: HashMap<String, Vec<(Range<u64>, GffRecord)>> = HashMap::new();
+    for r in records {
+      if !grouped.contains_key(&r.seq_id) {
+        seq_ids.push(r.seq_id.clone());
+      }
+      grouped.entry(r.seq_id.clone()).or_default().push((r.range(), r));
+    }
+    let trees = grouped.into_iter()
+      .map(|(id, rs)| (id, IntervalTree::new(rs)))
+      .collect();
+    GffIndex { seq_ids, trees }
+  }
+
+  /// Seqids with records, in the order they were first seen.
+  pub fn seq_ids(&self) -> impl Iterator<Item = &str> {
+    self.seq_ids.iter().map(String::as_str)
+  }
+
+  pub fn contains(&self, seq_id: &str) -> bool {
+    self.trees.contains_key(seq_id)
+  }
+
+  /// Every record on `seq_id`, in order of start.
+  pub fn records<'a>(&'a self, seq_id: &str) -> impl Iterator<Item = &'a GffRecord> {
+    self.trees.get(seq_id).into_iter().flat_map(|t| t.iter().map(|(_, r)| r))
+  }
+
+  /// The records on `seq_id` overlapping `range`, counting from 0 and
+  /// half-open, in order of start.
+  pub fn overlapping(&self, seq_id: &str, range: Range<u64>) -> Vec<&GffRecord> {
+    match self.trees.get(seq_id) {
+      Some(t) => t.overlapping(range).into_iter().map(|(_, r)| r).collect(),
+      None => Vec::new(),
+    }
+  }
+
+  /// The record at position `i` of [GffIndex::records] for `seq_id`.
+  pub fn record(&self, seq_id: &str, i: usize) -> Option<&GffRecord> {
+    self.trees.get(seq_id)?.get(i).map(|(_, r)| r)
+  }
+
+  /// Like [GffIndex::overlapping], but giving the positions of the records
+  /// for [GffIndex::record], in increasing order. These identify a record for
+  /// as long as the index lives.
+  pub fn overlapping_indices(&self, seq_id: &str, range: Range<u64>) -> Vec<usize> {
+    match self.trees.get(seq_id) {
+      Some(t) => t.overlapping_indices(range),
+      None => Vec::new(),
+    }
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+
+  #[test]
+  fn test_record_round_trip() {
+    let line = "ctg1\tprokka\tCDS\t10\t99\t0.5\t-\t2\tID=cds1;Parent=gene1;product=hypothetical protein";
+    let mut rec = GffRecord::from_str(line).unwrap();
+    assert_eq!(rec.to_string(), line);
+    assert_eq!((rec.start, rec.end, rec.strand), (OneBased::new(10), OneBased::new(99), Strand::Negative));
+
+    rec.attributes.set("partial", "true");
+    rec.attributes.set("ID", "cds2");
+    assert_eq!(rec.attributes.get("ID"), Some("cds2"));
+    assert!(rec.to_string().ends_with("\tID=cds2;Parent=gene1;product=hypothetical protein;partial=true"));
+
+    let bare = "ctg1\t.\tgene\t1\t5\t.\t?\t.\t.";
+    assert_eq!(GffRecord::from_str(bare).unwrap().to_string(), bare);
+  }
+
+  #[test]
+  fn test_reader_and_index() {
+    let input: &[u8] = b"##gff-version 3\n\
+      ctg2\t.\tgene\t100\t200\t.\t+\t.\tID=g1\n\
+      # comment\n\
+      ctg1\t.\tgene\t1\t50\t.\t+\t.\tID=g2\n\
+      ctg2\tbroken\n\
+      ctg2\t.\tgene\t10\t20\t.\t-\t.\tID=g3\n\
+      ##FASTA\n>ctg1\nACGT\n";
+    let (records, errors): (Vec<_>, Vec<_>) = GffReader::new(input).partition(Result::is_ok);
+    assert_eq!(records.len(), 3);
+    match &errors[..] {
+      [Err(GffError::Parse(e))] => assert_eq!((e.line, e.record), (5, Some(3))),
+      e => panic!("Expected one parse error but got {:?}", e),
+    }
+
+    let index = GffIndex::new(records.into_iter().map(Result::unwrap));
+    assert_eq!(index.seq_ids().collect::<Vec<_>>(), vec!["ctg2", "ctg1"]);
+    let ids = |rs: Vec<&GffRecord>| rs.iter().map(|r| r.attributes.get("ID").unwrap().to_string()).collect::<Vec<_>>();
+    assert_eq!(ids(index.records("ctg2").collect()), vec!["g3", "g1"]);
+    assert_eq!(ids(index.overlapping("ctg2", 19..100)), vec!["g3", "g1"]);
+    assert!(index.overlapping("ctg2", 20..99).is_empty());
+    assert!(index.overlapping("ctg3", 0..10).is_empty());
+    assert_eq!(index.overlapping_indices("ctg2", 19..100), vec![0, 1]);
+    assert_eq!(index.record("ctg2", 1).and_then(|r| r.attributes.get("ID")), Some("g1"));
+    assert!(index.record("ctg2", 2).is_none());
+  }
+}
@@ -0,0 +1,295 @@
+//! # FASTA header conventions
+//!
+//! Typed fields pulled out of the common ways that databases lay out FASTA
+//! description lines:
+//!
+//!   * NCBI pipe-delimited identifiers, such as `gi|123|ref|NC_000913.3|`
+//!   * UniProt headers, such as `sp|P12345|NAME_HUMAN Some protein OS=Homo sapiens OX=9606 GN=ABC`
+//!   * `key=value` descriptions, either bare or in `[key=value]` brackets
+//!
+//! Headers that follow none of these are read as a plain identifier with an
+//! optional `.version` suffix.
+//!
+//! See: https://ncbi.github.io/cxx-toolkit/pages/ch_demo#ch_demo.T5 and
+//! https://www.uniprot.org/help/fasta-headers
+
+use super::fasta::FastaDescription;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum HeaderConvention {
+  NcbiPipe,
+  UniProt,
+  KeyValue,
+  Plain,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct HeaderFields {
+  pub convention: HeaderConvention,
+  /// The source database tag, such as `ref`, `gb` or `sp`.
+  pub database: Option<String>,
+  /// The accession, without any version suffix.
+  pub accession: Option<String>,
+  pub version: Option<u32>,
+  /// The entry name or locus, such as `NAME_HUMAN`.
+  pub entry_name: Option<String>,
+  /// Free text from the description, without any `key=value` fields.
+  pub description: Option<String>,
+  pub organism: Option<String>,
+  pub taxon_id: Option<u64>,
+  pub gene: Option<String>,
+  /// Every `key=value` pair found in the description, in order.
+  pub attributes: Vec<(String, String)>,
+}
+
+/// NCBI database tags, and the number of fields that follow each.
+const NCBI_TAGS: &[(&str, usize)] = &[
+  ("gi", 1), ("lcl", 1), ("bbs", 1), ("bbm", 1), ("gim", 1),
+  ("gb", 2), ("emb", 2), ("dbj", 2), ("ref", 2), ("pir", 2), ("prf", 2),
+  ("sp", 2), ("tr", 2), ("pdb", 2), ("tpg", 2), ("tpe", 2), ("tpd", 2),
+  ("gnl", 2), ("pat", 3), ("pgp", 3),
+];
+
+impl HeaderFields {
+  /// Parse a description line, as stored in `FastaRecord::descr_line`.
+  pub fn read(descr_line: &str) -> HeaderFields {
+    HeaderFields::from_description(&FastaDescription::read(descr_line))
+  }
+
+  pub fn from_description(fd: &FastaDescription) -> HeaderFields {
+    let id = fd.identifier.as_deref().unwrap_or("");
+    let (description, attributes) = split_attributes(fd.description.as_deref().unwrap_or(""));
+
+    let mut fields = HeaderFields {
+      convention: HeaderConvention::Plain,
+      database: None,
+      accession: None,
+      version: None,
+      entry_name: None,
+      description,
+      organism: None,
+      taxon_id: None,
+      gene: None,
+      attributes,
+    };
+
+    let tags: Vec<&str> = id.split('|').collect();
+    if tags.len() > 1 && NCBI_TAGS.iter().any(|(t, _)| *t == tags[0]) {
+      fields.read_ncbi(&tags);
+    } else {
+      fields.set_accession(id);
+    }
+
+    if fields.convention == HeaderConvention::Plain && !fields.attributes.is_empty() {
+      fields.convention = HeaderConvention::KeyValue;
+    }
+    fields.read_attributes();
+
+    fields
+  }
+
+  /// The accession with its version suffix, if it has one.
+  pub fn versioned_accession(&self) -> Option<String> {
+    self.accession.as_ref().map(|a| match self.version {
+      Some(v) => format!("{}.{}", a, v),
+      None => a.clone(),
+    })
+  }
+
+  fn read_ncbi(&mut self, tags: &[&str]) {
+    self.convention = HeaderConvention::NcbiPipe;
+    let mut i = 0;
+    while i < tags.len() {
+      let tag = tags[i];
+      let arity = match NCBI_TAGS.iter().find(|(t, _)| *t == tag) {
+        Some((_, a)) => *a,
+        None => { i += 1; continue }
+      };
+      let values = &tags[(i + 1).min(tags.len())..(i + 1 + arity).min(tags.len())];
+
+      // `gi` numbers are only kept if there's nothing better
+      if tag != "gi" || self.database.is_none() {
+        self.database = Some(tag.to_string());
+        match tag {
+          "gnl" | "pat" | "pgp" => {
+            self.set_accession(values.get(1).copied().unwrap_or(""));
+            self.entry_name = None;
+          }
+          _ => {
+            self.set_accession(values.first().copied().unwrap_or(""));
+            self.entry_name = values.get(1)
+              .filter(|n| !n.is_empty())
+              .map(|n| n.to_string());
+          }
+        }
+      }
+      if tag == "sp" || tag == "tr" {
+        self.convention = HeaderConvention::UniProt;
+      }
+      i += 1 + arity;
+    }
+  }
+
+  fn set_accession(&mut self, acc: &str) {
+    let (acc, version) = split_version(acc);
+    self.accession = Some(acc.to_string()).filter(|a| !a.is_empty());
+    self.version = version;
+  }
+
+  fn read_attributes(&mut self) {
+    for (k, v) in &self.attributes {
+      match k.as_str() {
+        "OS" | "organism" => self.organism = Some(v.clone()),
+        "OX" | "taxid" | "tax_id" | "TaxID" | "taxon" => self.taxon_id = v.parse().ok(),
+        "GN" | "gene" => self.gene = Some(v.clone()),
+        "SV" if self.version.is_none() => self.version = v.parse().ok(),
+        _ => {}
+      }
+    }
+  }
+}
+
+/// Split an accession such as `NC_000913.3` into the accession and its
+/// numeric version suffix, if it has one.
+pub fn split_version(acc: &str) -> (&str, Option<u32>) {
+  match acc.rfind('.') {
+    Some(d) => match acc[d + 1..].parse::<u32>() {
+      Ok(v) => (&acc[..d], Some(v)),
+      Err(_) => (acc, None),
+    },
+    None => (acc, None),
+  }
+}
+
+/// Split a description into its free text and its `key=value` pairs.
+///
+/// Bare values run until the next ` key=`, so that `OS=Homo sapiens OX=9606`
+/// reads the organism as `Homo sapiens`. Bracketed pairs, as in
+/// `[organism=Escherichia coli]`, end at the closing bracket.
+fn split_attributes(descr: &str) -> (Option<String>, Vec<(String, String)>) {
+  let mut text = String::new();
+  let mut attributes = Vec::new();
+  let mut rest = descr;
+
+  // bracketed pairs can appear anywhere, so strip them out first
+  let mut unbracketed = String::new();
+  while let Some(open) = rest.find('[') {
+    match rest[open..].find(']').map(|c| &rest[open + 1..open + c]) {
+      Some(inner) if key_value(inner).is_some() => {
+        let (k, v) = key_value(inner).unwrap();
+        unbracketed.push_str(&rest[..open]);
+        attributes.push((k.to_string(), v.trim().to_string()));
+        rest = &rest[open + inner.len() + 2..];
+      }
+      _ => {
+        unbracketed.push_str(&rest[..=open]);
+        rest = &rest[open + 1..];
+      }
+    }
+  }
+  unbracketed.push_str(rest);
+
+  let mut current: Option<(&str, Vec<&str>)> = None;
+  for w in unbracketed.split_whitespace() {
+    match key_value(w) {
+      Some((k, v)) => {
+        if let Some((k, vs)) = current.take() { attributes.push((k.to_string(), vs.join(" "))) }
+        current = Some((k, Some(v).filter(|v| !v.is_empty()).into_iter().collect()));
+      }
+      None => match current.as_mut() {
+        Some((_, vs)) => vs.push(w),
+        None => {
+          if !text.is_empty() { text.push(' ') }
+          text.push_str(w);
+        }
+      }
+    }
+  }
+  if let Some((k, vs)) = current { attributes.push((k.to_string(), vs.join(" "))) }
+
+  (Some(text).filter(|t| !t.is_empty()), attributes)
+}
+
+fn key_value(s: &str) -> Option<(&str, &str)> {
+  let eq = s.find('=')?;
+  let (k, v) = (&s[..eq], &s[eq + 1..]);
+  let is_key = k.chars().next().is_some_and(|c| c.is_ascii_alphabetic()) &&
+    k.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+  if is_key { Some((k, v)) } else { None }
+}
+
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+
+  #[test]
+  fn test_ncbi_pipe() {
+    let f = HeaderFields::read("gi|49175990|ref|NC_000913.3| Escherichia coli K-12");
+    assert_eq!(f.convention, HeaderConvention::NcbiPipe);
+    assert_eq!(f.database.as_deref(), Some("ref"));
+    assert_eq!(f.accession.as_deref(), Some("NC_000913"));
+    assert_eq!(f.version, Some(3));
+    assert_eq!(f.versioned_accession().as_deref(), Some("NC_000913.3"));
+    assert_eq!(f.description.as_deref(), Some("Escherichia coli K-12"));
+
+    let f = HeaderFields::read("gb|M73307.1|AGMA13GT");
+    assert_eq!(f.database.as_deref(), Some("gb"));
+    assert_eq!(f.versioned_accession().as_deref(), Some("M73307.1"));
+    assert_eq!(f.entry_name.as_deref(), Some("AGMA13GT"));
+
+    let f = HeaderFields::read("gi|123");
+    assert_eq!(f.database.as_deref(), Some("gi"));
+    assert_eq!(f.accession.as_deref(), Some("123"));
+  }
+
+  #[test]
+  fn test_uniprot() {
+    let f = HeaderFields::read(
+      "sp|P69905|HBA_HUMAN Hemoglobin subunit alpha OS=Homo sapiens OX=9606 GN=HBA1 PE=1 SV=2");
+    assert_eq!(f.convention, HeaderConvention::UniProt);
+    assert_eq!(f.database.as_deref(), Some("sp"));
+    assert_eq!(f.accession.as_deref(), Some("P69905"));
+    assert_eq!(f.version, Some(2));
+    assert_eq!(f.entry_name.as_deref(), Some("HBA_HUMAN"));
+    assert_eq!(f.description.as_deref(), Some("Hemoglobin subunit alpha"));
+    assert_eq!(f.organism.as_deref(), Some("Homo sapiens"));
+    assert_eq!(f.taxon_id, Some(9606));
+    assert_eq!(f.gene.as_deref(), Some("HBA1"));
+  }
+
+  #[test]
+  fn test_key_value() {
+    let f = HeaderFields::read("contig_1.2 assembled contig [organism=Escherichia coli] [gene=lacZ] taxid=562 cov=12.5");
+    assert_eq!(f.convention, HeaderConvention::KeyValue);
+    assert_eq!(f.accession.as_deref(), Some("contig_1"));
+    assert_eq!(f.version, Some(2));
+    assert_eq!(f.description.as_deref(), Some("assembled contig"));
+    assert_eq!(f.organism.as_deref(), Some("Escherichia coli"));
+    assert_eq!(f.gene.as_deref(), Some("lacZ"));
+    assert_eq!(f.taxon_id, Some(562));
+    assert_eq!(f.attributes, vec![
+      ("organism".to_string(), "Escherichia coli".to_string()),
+      ("gene".to_string(), "lacZ".to_string()),
+      ("taxid".to_string(), "562".to_string()),
+      ("cov".to_string(), "12.5".to_string()),
+    ]);
+  }
+
+  #[test]
+  fn test_split_version() {
+    assert_eq!(split_version("NC_000913.3"), ("NC_000913", Some(3)));
+    assert_eq!(split_version("contig.a"), ("contig.a", None));
+    assert_eq!(split_version("P69905"), ("P69905", None));
+  }
+
+  #[test]
+  fn test_plain() {
+    let f = HeaderFields::read("scaffold_12 a [bracketed] note");
+    assert_eq!(f.convention, HeaderConvention::Plain);
+    assert_eq!(f.accession.as_deref(), Some("scaffold_12"));
+    assert_eq!(f.version, None);
+    assert_eq!(f.description.as_deref(), Some("a [bracketed] note"));
+  }
+}
@@ -1,5 +1,12 @@
+pub mod bed;
 pub mod embl;
+pub mod error;
 pub mod fasta;
+pub mod fastq;
 pub mod feature_table;
 pub mod gff3;
-pub mod parser;
\ No newline at end of file
+pub mod header;
+pub mod packed;
+pub mod parser;
+pub mod record;
+pub mod typed;
\ No newline at end of file
@@ -0,0 +1,197 @@
+//! # 2-bit packed DNA
+//!
+//! Stores `ACGT` at four bases per byte. As in the UCSC `.2bit` format, runs
+//! of `N` and runs of lower case (soft-masked) bases are kept as separate
+//! lists of intervals, so a sequence round-trips exactly as long as it only
+//! holds `ACGTN` in either case.
+
+use std::ops::Range;
+
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct PackedDna {
+  len: usize,
+  bases: Vec<u8>,
+  /// Runs of `N`, sorted and non-overlapping. Bases under them are stored as `A`.
+  n_runs: Vec<Range<usize>>,
+  /// Runs of lower case bases, sorted and non-overlapping.
+  mask_runs: Vec<Range<usize>>,
+}
+
+fn encode(b: u8) -> Option<u8> {
+  match b.to_ascii_uppercase() {
+    b'A' => Some(0),
+    b'C' => Some(1),
+    b'G' => Some(2),
+    b'T' => Some(3),
+    _ => None,
+  }
+}
+
+const DECODE: [u8; 4] = [b'A', b'C', b'G', b'T'];
+
+/// Add `i` to the last run if it follows on, or start a new one.
+fn extend_runs(runs: &mut Vec<Range<usize>>, i: usize) {
+  match runs.last_mut() {
+    Some(r) if r.end == i => r.end += 1,
+    _ => runs.push(i..i + 1),
+  }
+}
+
+fn in_runs(runs: &[Range<usize>], i: usize) -> bool {
+  let at = runs.partition_point(|r| r.end <= i);
+  runs.get(at).is_some_and(|r| r.start <= i)
+}
+
+/// The parts of `runs` within `from..to`.
+fn runs_within(runs: &[Range<usize>], from: usize, to: usize) -> impl Iterator<Item = Range<usize>> + '_ {
+  runs[runs.partition_point(|r| r.end <= from)..].iter()
+    .take_while(move |r| r.start < to)
+    .map(move |r| r.start.max(from)..r.end.min(to))
+}
+
+/// Runs at the same bases of the reversed sequence of length `len`.
+fn reverse_runs(runs: &[Range<usize>], len: usize) -> Vec<Range<usize>> {
+  runs.iter().rev().map(|r| len - r.end..len - r.start).collect()
+}
+
+impl PackedDna {
+  /// Pack a sequence, failing with the offset and value of the first
+  /// character that isn't one of `ACGTN`.
+  pub fn pack(seq: &str) -> Result<PackedDna, (usize, char)> {
+    let mut packed = PackedDna {
+      len: seq.len(),
+      bases: vec![0; seq.len().div_ceil(4)],
+      n_runs: Vec::new(),
+      mask_runs: Vec::new(),
+    };
+
+    for (i, b) in seq.bytes().enumerate() {
+      let code = match encode(b) {
+        Some(c) => c,
+        None if b.eq_ignore_ascii_case(&b'N') => {
+          extend_runs(&mut packed.n_runs, i);
+          0
+        }
+        None => return Err((i, seq[i..].chars().next().unwrap())),
+      };
+      if b.is_ascii_lowercase() {
+        extend_runs(&mut packed.mask_runs, i);
+      }
+      packed.bases[i / 4] |= code << (2 * (i % 4));
+    }
+
+    Ok(packed)
+  }
+
+  pub fn len(&self) -> usize {
+    self.len
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.len == 0
+  }
+
+  pub fn n_runs(&self) -> &[Range<usize>] {
+    &self.n_runs
+  }
+
+  pub fn mask_runs(&self) -> &[Range<usize>] {
+    &self.mask_runs
+  }
+
+  /// The 2-bit code of the base at offset `i`.
+  fn code(&self, i: usize) -> u8 {
+    (self.bases[i / 4] >> (2 * (i % 4))) & 3
+  }
+
+  /// The base at offset `i`, counting from 0.
+  pub fn get(&self, i: usize) -> Option<u8> {
+    if i >= self.len { return None }
+    let b = if in_runs(&self.n_runs, i) {
+      b'N'
+    } else {
+      DECODE[self.code(i) as usize]
+    };
+    Some(if in_runs(&self.mask_runs, i) { b.to_ascii_lowercase() } else { b })
+  }
+
+  /// Unpack the bases from `from` up to `to`, counting from 0.
+  ///
+  /// Panics if the range is out of bounds.
+  pub fn slice(&self, from: usize, to: usize) -> String {
+    assert!(from <= to && to <= self.len, "Range {}..{} is out of bounds for length {}", from, to, self.len);
+    let mut out: Vec<u8> = (from..to).map(|i| DECODE[self.code(i) as usize]).collect();
+    for r in runs_within(&self.n_runs, from, to) {
+      for i in r { out[i - from] = b'N' }
+    }
+    for r in runs_within(&self.mask_runs, from, to) {
+      for i in r { out[i - from].make_ascii_lowercase() }
+    }
+    String::from_utf8(out).unwrap()
+  }
+
+  /// The reverse complement, worked out on the packed bases. `N` and
+  /// soft-masked runs are carried over to the reversed positions.
+  pub fn reverse_complement(&self) -> PackedDna {
+    let n_runs = reverse_runs(&self.n_runs, self.len);
+    let mut bases = vec![0; self.bases.len()];
+    for i in 0..self.len {
+      // A, C, G and T are 0 to 3, so 3 minus a code is its complement
+      bases[i / 4] |= (3 - self.code(self.len - 1 - i)) << (2 * (i % 4));
+    }
+    // as when packing, bases under runs of N are stored as A
+    for i in n_runs.iter().flat_map(Range::clone) {
+      bases[i / 4] &= !(3 << (2 * (i % 4)));
+    }
+    PackedDna { len: self.len, bases, n_runs, mask_runs: reverse_runs(&self.mask_runs, self.len) }
+  }
+
+  pub fn unpack(&self) -> String {
+    self.slice(0, self.len)
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+
+  #[test]
+  fn test_pack_round_trip() {
+    let seq = "ACGTNNNacgtnNAC";
+    let packed = PackedDna::pack(seq).unwrap();
+    assert_eq!(packed.len(), 15);
+    assert_eq!(packed.bases.len(), 4);
+    assert_eq!(packed.n_runs(), &[4..7, 11..13]);
+    assert_eq!(packed.mask_runs(), &[Range { start: 7, end: 12 }]);
+    assert_eq!(packed.unpack(), seq);
+    assert_eq!(packed.slice(5, 10), "NNacg");
+    assert_eq!(packed.get(11), Some(b'n'));
+    assert_eq!(packed.get(15), None);
+  }
+
+  #[test]
+  fn test_slice_with_many_runs() {
+    let seq: String = (0..400).map(|i| match i % 7 { 0 => 'N', 1 | 2 => 'a', 3 => 'C', 4 => 'g', _ => 'T' }).collect();
+    let packed = PackedDna::pack(&seq).unwrap();
+    for (from, to) in [(0, 400), (3, 9), (50, 51), (399, 400), (100, 100)] {
+      assert_eq!(packed.slice(from, to), seq[from..to]);
+    }
+  }
+
+  #[test]
+  fn test_reverse_complement() {
+    let seq = "ACGTNNNacgtnNACg";
+    let rc = PackedDna::pack(seq).unwrap().reverse_complement();
+    assert_eq!(rc.unpack(), "cGTNnacgtNNNACGT");
+    assert_eq!(rc, PackedDna::pack("cGTNnacgtNNNACGT").unwrap());
+    assert_eq!(rc.reverse_complement(), PackedDna::pack(seq).unwrap());
+    assert_eq!(PackedDna::default().reverse_complement(), PackedDna::default());
+  }
+
+  #[test]
+  fn test_pack_rejects_ambiguity_codes() {
+    assert_eq!(PackedDna::pack("ACGRT"), Err((3, 'R')));
+  }
+}
@@ -0,0 +1,375 @@
+//! Format-agnostic access to sequence records, so that tools can accept either
+//! FASTA or FASTQ input and write records back out in the format they came in.
+
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::io::{self, BufRead};
+use std::ops::Range;
+use std::str::FromStr;
+
+use crate::alphabet::{InvalidResidue, Validator};
+
+use super::error::ParseError;
+use super::fasta::{FastaDescription, FastaError, FastaFormat, FastaReader, FastaRecord};
+use super::fastq::{FastqError, FastqReader, FastqRecord};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum SeqFormat {
+  Fasta,
+  Fastq,
+}
+
+impl SeqFormat {
+  /// Guess the format from the first non-whitespace byte of the input,
+  /// without consuming anything. Anything but FASTQ is taken to be FASTA.
+  ///
+  /// Returns `None` if the buffered input is empty or only whitespace.
+  pub fn detect<R : BufRead>(reader: &mut R) -> Result<Option<SeqFormat>, io::Error> {
+    Ok(FileFormat::detect(reader)?.map(|f| f.seq_format().unwrap_or(SeqFormat::Fasta)))
+  }
+}
+
+/// The sequence and annotation formats that can be told apart by the start of
+/// a file.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum FileFormat {
+  Fasta,
+  Fastq,
+  Embl,
+  GenBank,
+  Gff3,
+}
+
+impl FromStr for FileFormat {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "fasta" => Ok(FileFormat::Fasta),
+      "fastq" => Ok(FileFormat::Fastq),
+      "embl" => Ok(FileFormat::Embl),
+      "genbank" => Ok(FileFormat::GenBank),
+      "gff3" => Ok(FileFormat::Gff3),
+      e => Err(format!("Unknown format `{}'. Expected fasta, fastq, embl, genbank or gff3", e)),
+    }
+  }
+}
+
+impl FileFormat {
+  /// Guess the format from the start of the first non-blank line, without
+  /// consuming anything. Anything unrecognised is taken to be FASTA.
+  ///
+  /// Returns `None` if the buffered input is empty or only whitespace.
+  pub fn detect<R : BufRead>(reader: &mut R) -> Result<Option<FileFormat>, io::Error> {
+    let buf = reader.fill_buf()?;
+    let start = match buf.iter().position(|c| !c.is_ascii_whitespace()) {
+      Some(start) => &buf[start..],
+      None => return Ok(None),
+    };
+    let first_line = start.split(|c| *c == b'\n').next().unwrap_or(start);
+    Ok(Some(
+      if start.starts_with(b"@") {
+        FileFormat::Fastq
+      } else if start.starts_with(b">") {
+        FileFormat::Fasta
+      } else if start.starts_with(b"ID   ") {
+        FileFormat::Embl
+      } else if start.starts_with(b"LOCUS ") {
+        FileFormat::GenBank
+      } else if start.starts_with(b"##gff-version") || first_line.split(|c| *c == b'\t').count() == 9 {
+        FileFormat::Gff3
+      } else {
+        FileFormat::Fasta
+      }))
+  }
+
+  /// The sequence format, for the formats read as sequence records.
+  pub fn seq_format(self) -> Option<SeqFormat> {
+    match self {
+      FileFormat::Fasta => Some(SeqFormat::Fasta),
+      FileFormat::Fastq => Some(SeqFormat::Fastq),
+      _ => None,
+    }
+  }
+}
+
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum SeqRecord {
+  Fasta(FastaRecord),
+  Fastq(FastqRecord),
+}
+
+impl SeqRecord {
+  pub fn seq(&self) -> &str {
+    match self {
+      SeqRecord::Fasta(r) => &r.seq,
+      SeqRecord::Fastq(r) => &r.seq,
+    }
+  }
+
+  /// The encoded qualities of a FASTQ record.
+  pub fn qualities(&self) -> Option<&str> {
+    match self {
+      SeqRecord::Fasta(_) => None,
+      SeqRecord::Fastq(r) => Some(&r.qualities),
+    }
+  }
+
+  pub fn description(&self) -> FastaDescription {
+    match self {
+      SeqRecord::Fasta(r) => FastaDescription::read(&r.descr_line),
+      SeqRecord::Fastq(r) => FastaDescription {
+        identifier: Some(r.id.clone()),
+        description: r.description.clone(),
+      },
+    }
+  }
+
+  /// Replace the identifier, keeping any description.
+  pub fn set_identifier(&mut self, id: &str) {
+    match self {
+      SeqRecord::Fasta(r) => {
+        let fd = FastaDescription::read(&r.descr_line);
+        r.descr_line = FastaRecord::descr_line(Some(id), fd.description.as_deref());
+      }
+      SeqRecord::Fastq(r) => r.id = id.to_string(),
+    }
+  }
+
+  /// Replace the sequence, such as with a masked copy. For FASTQ, it must be
+  /// the same length so that it still matches the qualities.
+  pub fn set_seq(&mut self, seq: String) {
+    match self {
+      SeqRecord::Fasta(r) => r.seq = seq,
+      SeqRecord::Fastq(r) => {
+        assert_eq!(seq.len(), r.qualities.len(), "Replacement fastq sequence must match the quality length");
+        r.seq = seq
+      }
+    }
+  }
+
+  /// Replace the description, keeping the identifier.
+  pub fn set_description(&mut self, descr: Option<&str>) {
+    match self {
+      SeqRecord::Fasta(r) => {
+        let fd = FastaDescription::read(&r.descr_line);
+        r.descr_line = FastaRecord::descr_line(fd.identifier.as_deref(), descr);
+      }
+      SeqRecord::Fastq(r) => r.description = descr.map(String::from),
+    }
+  }
+
+  /// A copy of this record holding only the bases from `from` up to `to`,
+  /// counting from 0. Qualities are clipped along with the sequence.
+  pub fn sub_record(&self, from: usize, to: usize) -> SeqRecord {
+    match self {
+      SeqRecord::Fasta(r) => SeqRecord::Fasta(FastaRecord {
+        descr_line: r.descr_line.clone(),
+        seq: r.seq[from..to].to_string(),
+      }),
+      SeqRecord::Fastq(r) => SeqRecord::Fastq(FastqRecord {
+        seq: r.seq[from..to].to_string(),
+        qualities: r.qualities[from..to].to_string(),
+        ..r.clone()
+      }),
+    }
+  }
+
+  /// A copy of this record holding the bases of each range in `keep` joined
+  /// together in order, counting from 0. Qualities are clipped along with the
+  /// sequence.
+  pub fn spliced(&self, keep: &[Range<usize>]) -> SeqRecord {
+    let join = |s: &str| keep.iter().map(|r| &s[r.clone()]).collect::<String>();
+    match self {
+      SeqRecord::Fasta(r) => SeqRecord::Fasta(FastaRecord {
+        descr_line: r.descr_line.clone(),
+        seq: join(&r.seq),
+      }),
+      SeqRecord::Fastq(r) => SeqRecord::Fastq(FastqRecord {
+        seq: join(&r.seq),
+        qualities: join(&r.qualities),
+        ..r.clone()
+      }),
+    }
+  }
+
+  /// Write the record in the format it was read in.
+  pub fn write<W : io::Write>(&self, fasta: &FastaFormat, out: &mut W) -> Result<(), io::Error> {
+    match self {
+      SeqRecord::Fasta(r) => r.write(fasta, out),
+      SeqRecord::Fastq(r) => r.write(out),
+    }
+  }
+
+  pub fn into_fasta(self) -> FastaRecord {
+    match self {
+      SeqRecord::Fasta(r) => r,
+      SeqRecord::Fastq(r) => r.into(),
+    }
+  }
+}
+
+
+#[derive(Debug)]
+pub enum SeqError {
+  Fasta(FastaError),
+  Fastq(FastqError),
+}
+
+impl SeqError {
+  /// The parse error, if this is one that reading can carry on after.
+  pub fn into_parse_error(self) -> Result<ParseError, SeqError> {
+    match self {
+      SeqError::Fasta(FastaError::Parse(e)) | SeqError::Fastq(FastqError::Parse(e)) => Ok(e),
+      e => Err(e),
+    }
+  }
+}
+
+impl Display for SeqError {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    match self {
+      SeqError::Fasta(e) => e.fmt(f),
+      SeqError::Fastq(e) => e.fmt(f),
+    }
+  }
+}
+
+impl Error for SeqError {
+  fn source(&self) -> Option<&(dyn Error + 'static)> {
+    match self {
+      SeqError::Fasta(e) => Some(e),
+      SeqError::Fastq(e) => Some(e),
+    }
+  }
+}
+
+impl From<SeqError> for io::Error {
+  fn from(e: SeqError) -> Self {
+    match e {
+      SeqError::Fasta(e) => e.into(),
+      SeqError::Fastq(e) => e.into(),
+    }
+  }
+}
+
+
+/// Streaming reader over either FASTA or FASTQ input.
+pub enum SeqReader<R> {
+  Fasta(FastaReader<R>),
+  Fastq(FastqReader<R>),
+}
+
+impl <R : BufRead> SeqReader<R> {
+  /// Open a reader, detecting the format from the start of the input.
+  ///
+  /// Empty input is treated as FASTA.
+  pub fn new(mut reader: R) -> Result<SeqReader<R>, io::Error> {
+    let format = SeqFormat::detect(&mut reader)?.unwrap_or(SeqFormat::Fasta);
+    Ok(SeqReader::with_format(reader, format))
+  }
+
+  pub fn with_format(reader: R, format: SeqFormat) -> SeqReader<R> {
+    match format {
+      SeqFormat::Fasta => SeqReader::Fasta(FastaReader::new(reader)),
+      SeqFormat::Fastq => SeqReader::Fastq(FastqReader::new(reader)),
+    }
+  }
+
+  /// Check sequence lines against an alphabet as they are read.
+  pub fn with_validator(self, validator: Option<Validator>) -> SeqReader<R> {
+    match self {
+      SeqReader::Fasta(r) => SeqReader::Fasta(r.with_validator(validator)),
+      SeqReader::Fastq(r) => SeqReader::Fastq(r.with_validator(validator)),
+    }
+  }
+
+  /// Take the residues kept under [crate::alphabet::Policy::Warn] since this
+  /// was last called.
+  pub fn take_warnings(&mut self) -> Vec<InvalidResidue> {
+    match self {
+      SeqReader::Fasta(r) => r.take_warnings(),
+      SeqReader::Fastq(r) => r.take_warnings(),
+    }
+  }
+}
+
+impl <R : BufRead> Iterator for SeqReader<R> {
+  type Item = Result<SeqRecord, SeqError>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    match self {
+      SeqReader::Fasta(r) => r.next().map(|r| r.map(SeqRecord::Fasta).map_err(SeqError::Fasta)),
+      SeqReader::Fastq(r) => r.next().map(|r| r.map(SeqRecord::Fastq).map_err(SeqError::Fastq)),
+    }
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+
+  #[test]
+  fn test_detect_file_format() {
+    let detect = |s: &str| FileFormat::detect(&mut s.as_bytes()).unwrap();
+    assert_eq!(detect("\nID   X56734; SV 1; linear; mRNA; STD; PLN; 1859 BP.\n"), Some(FileFormat::Embl));
+    assert_eq!(detect("LOCUS       SCU49845     5028 bp    DNA             PLN       21-JUN-1999\n"), Some(FileFormat::GenBank));
+    assert_eq!(detect("##gff-version 3\n"), Some(FileFormat::Gff3));
+    assert_eq!(detect("ctg1\t.\tgene\t1\t10\t.\t+\t.\tID=g1\n"), Some(FileFormat::Gff3));
+    assert_eq!(detect("@r1\nACGT\n+\nIIII\n"), Some(FileFormat::Fastq));
+    assert_eq!(detect(">s1\nACGT\n"), Some(FileFormat::Fasta));
+    assert_eq!(detect("ACGT\n"), Some(FileFormat::Fasta));
+    assert_eq!(detect(" \n"), None);
+
+    let detect_seq = |s: &str| SeqFormat::detect(&mut s.as_bytes()).unwrap();
+    assert_eq!(detect_seq("ID   X56734;\n"), Some(SeqFormat::Fasta));
+    assert_eq!(detect_seq("@r1\n"), Some(SeqFormat::Fastq));
+  }
+
+  #[test]
+  fn test_seq_reader_detects_format() {
+    let fasta: &[u8] = b"\n>id\nacgt\n";
+    let fastq: &[u8] = b"@id\nacgt\n+\nIIII\n";
+
+    match SeqReader::new(fasta).unwrap().next() {
+      Some(Ok(SeqRecord::Fasta(r))) => assert_eq!(r.seq, "acgt"),
+      r => panic!("Expected a fasta record but got {:?}", r)
+    }
+    match SeqReader::new(fastq).unwrap().next() {
+      Some(Ok(SeqRecord::Fastq(r))) => assert_eq!(r.qualities, "IIII"),
+      r => panic!("Expected a fastq record but got {:?}", r)
+    }
+  }
+
+  #[test]
+  fn test_sub_record_clips_qualities() {
+    let mut rec = SeqRecord::Fastq(FastqRecord {
+      id: "r1".to_string(),
+      description: Some("read".to_string()),
+      seq: "ACGT".to_string(),
+      qualities: "!+5I".to_string(),
+    });
+    rec.set_identifier("r1_clipped");
+    rec.set_description(Some("clipped read"));
+    let mut out = Vec::new();
+    rec.sub_record(1, 3).write(&FastaFormat::new(), &mut out).unwrap();
+    assert_eq!(out, b"@r1_clipped clipped read\nCG\n+\n+5\n");
+  }
+
+  #[test]
+  fn test_spliced_joins_ranges() {
+    let rec = SeqRecord::Fastq(FastqRecord {
+      id: "r1".to_string(),
+      description: None,
+      seq: "AACCGGTT".to_string(),
+      qualities: "!!++55II".to_string(),
+    });
+    match rec.spliced(&[0..2, 6..8]) {
+      SeqRecord::Fastq(r) => assert_eq!((r.seq.as_str(), r.qualities.as_str()), ("AATT", "!!II")),
+      r => panic!("Expected a fastq record but got {:?}", r)
+    }
+  }
+}
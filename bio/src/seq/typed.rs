@@ -0,0 +1,271 @@
+//! # Typed sequences
+//!
+//! [Seq] tags a sequence with its alphabet, so that DNA can't be passed where
+//! protein is expected. Residues are checked against the alphabet when the
+//! sequence is built, and DNA can optionally be held 2-bit packed with
+//! [Seq::pack].
+//!
+//! ```
+//! use bio::seq::typed::{Dna, Protein, Seq};
+//!
+//! let dna: Seq<Dna> = Seq::new("ATGGCCtaa").unwrap();
+//! let protein: Seq<Protein> = dna.translate(0);
+//! assert_eq!(protein.to_string(), "MA*");
+//! assert_eq!(dna.reverse_complement().to_string(), "ttaGGCCAT");
+//! ```
+
+use std::borrow::Cow;
+use std::convert::TryFrom;
+use std::fmt::{self, Display, Formatter};
+use std::marker::PhantomData;
+
+use crate::alphabet::Alphabet;
+use crate::dna::{complement, TRANSLATION_TABLE};
+
+use super::embl::Embl;
+use super::fasta::FastaRecord;
+use super::packed::PackedDna;
+
+/// Type-level tag for the alphabet of a [Seq].
+pub trait SeqAlphabet {
+  /// The residues that sequences with this tag may hold.
+  const ALPHABET: Alphabet;
+}
+
+/// DNA, including the IUPAC ambiguity codes.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Dna;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Rna;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Protein;
+
+impl SeqAlphabet for Dna {
+  const ALPHABET: Alphabet = Alphabet::IupacDna;
+}
+
+impl SeqAlphabet for Rna {
+  const ALPHABET: Alphabet = Alphabet::Rna;
+}
+
+impl SeqAlphabet for Protein {
+  const ALPHABET: Alphabet = Alphabet::Protein;
+}
+
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+enum Residues {
+  Text(String),
+  Packed(PackedDna),
+}
+
+/// A sequence whose residues are all in the alphabet `A`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Seq<A> {
+  residues: Residues,
+  alphabet: PhantomData<A>,
+}
+
+impl <A : SeqAlphabet> Seq<A> {
+  /// Build a sequence, failing with the offset and value of the first residue
+  /// that isn't in the alphabet.
+  pub fn new<S : Into<String>>(seq: S) -> Result<Seq<A>, (usize, char)> {
+    let seq = seq.into();
+    match A::ALPHABET.invalid_positions(&seq).first() {
+      Some(&i) => Err((i, seq[i..].chars().next().unwrap())),
+      None => Ok(Seq::new_unchecked(seq)),
+    }
+  }
+
+  /// Build a sequence without checking its residues.
+  pub fn new_unchecked<S : Into<String>>(seq: S) -> Seq<A> {
+    Seq { residues: Residues::Text(seq.into()), alphabet: PhantomData }
+  }
+
+  pub fn len(&self) -> usize {
+    match &self.residues {
+      Residues::Text(s) => s.len(),
+      Residues::Packed(p) => p.len(),
+    }
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+
+  /// The residues as text, unpacking them if needed.
+  pub fn as_str(&self) -> Cow<'_, str> {
+    match &self.residues {
+      Residues::Text(s) => Cow::Borrowed(s),
+      Residues::Packed(p) => Cow::Owned(p.unpack()),
+    }
+  }
+
+  /// The residues from `from` up to `to`, counting from 0.
+  pub fn slice(&self, from: usize, to: usize) -> Cow<'_, str> {
+    match &self.residues {
+      Residues::Text(s) => Cow::Borrowed(&s[from..to]),
+      Residues::Packed(p) => Cow::Owned(p.slice(from, to)),
+    }
+  }
+
+  pub fn into_string(self) -> String {
+    match self.residues {
+      Residues::Text(s) => s,
+      Residues::Packed(p) => p.unpack(),
+    }
+  }
+
+  /// A fasta record holding this sequence.
+  pub fn into_record(self, descr_line: &str) -> FastaRecord {
+    FastaRecord { descr_line: descr_line.to_string(), seq: self.into_string() }
+  }
+}
+
+impl <A> Display for Seq<A> where A : SeqAlphabet {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    f.write_str(&self.as_str())
+  }
+}
+
+impl <A : SeqAlphabet> TryFrom<&FastaRecord> for Seq<A> {
+  type Error = (usize, char);
+
+  fn try_from(record: &FastaRecord) -> Result<Self, Self::Error> {
+    Seq::new(record.seq.as_str())
+  }
+}
+
+impl <A : SeqAlphabet> TryFrom<&Embl> for Seq<A> {
+  type Error = (usize, char);
+
+  fn try_from(embl: &Embl) -> Result<Self, Self::Error> {
+    Seq::new(embl.sequence.as_str())
+  }
+}
+
+impl <A : SeqAlphabet> From<Seq<A>> for String {
+  fn from(seq: Seq<A>) -> Self {
+    seq.into_string()
+  }
+}
+
+
+/// Translate codons starting at `phase`, ignoring any trailing partial codon.
+/// Codons holding ambiguity codes translate to `X`.
+fn translate_codons(seq: &str, phase: usize) -> Seq<Protein> {
+  let bytes = seq.as_bytes();
+  let protein: String = (phase..)
+    .step_by(3)
+    .take_while(|i| i + 3 <= bytes.len())
+    .map(|i| {
+      let codon: String = bytes[i..i + 3].iter()
+        .map(|b| match b.to_ascii_lowercase() { b'u' => 't', b => b as char })
+        .collect();
+      TRANSLATION_TABLE.get(&codon).copied().unwrap_or("X")
+    })
+    .collect();
+  Seq::new_unchecked(protein)
+}
+
+impl Seq<Dna> {
+  /// Hold the sequence 2-bit packed, failing with the offset and value of the
+  /// first ambiguity code other than `N`.
+  pub fn pack(self) -> Result<Seq<Dna>, (usize, char)> {
+    let packed = match self.residues {
+      Residues::Text(s) => PackedDna::pack(&s)?,
+      Residues::Packed(p) => p,
+    };
+    Ok(Seq { residues: Residues::Packed(packed), alphabet: PhantomData })
+  }
+
+  pub fn is_packed(&self) -> bool {
+    matches!(self.residues, Residues::Packed(_))
+  }
+
+  /// Hold the sequence as text.
+  pub fn unpack(self) -> Seq<Dna> {
+    Seq::new_unchecked(self.into_string())
+  }
+
+  /// The reverse complement, packed if this sequence is.
+  pub fn reverse_complement(&self) -> Seq<Dna> {
+    match &self.residues {
+      Residues::Text(s) => Seq::new_unchecked(s.chars().rev().map(complement).collect::<String>()),
+      Residues::Packed(p) => Seq { residues: Residues::Packed(p.reverse_complement()), alphabet: PhantomData },
+    }
+  }
+
+  pub fn translate(&self, phase: usize) -> Seq<Protein> {
+    translate_codons(&self.as_str(), phase)
+  }
+
+  pub fn transcribe(&self) -> Seq<Rna> {
+    Seq::new_unchecked(self.as_str().replace('T', "U").replace('t', "u"))
+  }
+}
+
+impl Seq<Rna> {
+  pub fn reverse_complement(&self) -> Seq<Rna> {
+    Seq::new_unchecked(self.as_str().chars().rev()
+      .map(|n| match complement(n) { 'T' => 'U', 't' => 'u', c => c })
+      .collect::<String>())
+  }
+
+  pub fn translate(&self, phase: usize) -> Seq<Protein> {
+    translate_codons(&self.as_str(), phase)
+  }
+
+  pub fn back_transcribe(&self) -> Seq<Dna> {
+    Seq::new_unchecked(self.as_str().replace('U', "T").replace('u', "t"))
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+
+  #[test]
+  fn test_alphabet_checked() {
+    assert!(Seq::<Dna>::new("ACGTRYN").is_ok());
+    assert_eq!(Seq::<Dna>::new("ACGU"), Err((3, 'U')));
+    assert_eq!(Seq::<Rna>::new("ACGT"), Err((3, 'T')));
+    assert!(Seq::<Protein>::new("MKVL*").is_ok());
+  }
+
+  #[test]
+  fn test_packed_ops() {
+    let seq = Seq::<Dna>::new("ATGNNNaaa").unwrap().pack().unwrap();
+    assert!(seq.is_packed());
+    assert_eq!(seq.len(), 9);
+    assert_eq!(seq.slice(2, 5), "GNN");
+
+    let rc = seq.reverse_complement();
+    assert!(rc.is_packed());
+    assert_eq!(rc.to_string(), "tttNNNCAT");
+    assert_eq!(seq.translate(0).to_string(), "MXK");
+    assert!(Seq::<Dna>::new("ACR").unwrap().pack().is_err());
+  }
+
+  #[test]
+  fn test_rna() {
+    let rna = Seq::<Dna>::new("ATGGCA").unwrap().transcribe();
+    assert_eq!(rna.to_string(), "AUGGCA");
+    assert_eq!(rna.reverse_complement().to_string(), "UGCCAU");
+    assert_eq!(rna.translate(0).to_string(), "MA");
+    assert_eq!(rna.back_transcribe().to_string(), "ATGGCA");
+  }
+
+  #[test]
+  fn test_record_conversions() {
+    let record = FastaRecord { descr_line: "s1".to_string(), seq: "ACGT".to_string() };
+    let seq = Seq::<Dna>::try_from(&record).unwrap();
+    assert_eq!(seq.clone().into_record("s1"), record);
+    assert!(Seq::<Rna>::try_from(&record).is_err());
+    assert_eq!(String::from(seq), "ACGT");
+  }
+}
@@ -0,0 +1,126 @@
+//! # Sequence composition
+//!
+//! Simple per-sequence statistics, ignoring case.
+
+fn is_unambiguous(b: u8) -> bool {
+  matches!(b.to_ascii_uppercase(), b'A' | b'C' | b'G' | b'T' | b'U')
+}
+
+/// The fraction of `G` and `C` among the unambiguous bases, or 0 if there are
+/// none.
+pub fn gc_content(seq: &str) -> f64 {
+  let (gc, total) = seq.bytes()
+    .filter(|b| is_unambiguous(*b))
+    .fold((0, 0), |(gc, total), b| match b.to_ascii_uppercase() {
+      b'G' | b'C' => (gc + 1, total + 1),
+      _ => (gc, total + 1),
+    });
+  if total == 0 { 0.0 } else { gc as f64 / total as f64 }
+}
+
+/// The fraction of bases that aren't `ACGTU`, such as `N` and the IUPAC
+/// ambiguity codes, or 0 for an empty sequence.
+pub fn ambiguous_fraction(seq: &str) -> f64 {
+  if seq.is_empty() { return 0.0 }
+  seq.bytes().filter(|b| !is_unambiguous(*b)).count() as f64 / seq.len() as f64
+}
+
+/// The length of the longest run of a single residue.
+pub fn max_homopolymer(seq: &str) -> usize {
+  let mut longest = 0;
+  let mut run = 0;
+  let mut last = None;
+  for b in seq.bytes().map(|b| b.to_ascii_uppercase()) {
+    run = if last == Some(b) { run + 1 } else { 1 };
+    last = Some(b);
+    longest = longest.max(run);
+  }
+  longest
+}
+
+/// The number of `N` bases.
+pub fn n_count(seq: &str) -> usize {
+  seq.bytes().filter(|b| b.eq_ignore_ascii_case(&b'N')).count()
+}
+
+
+/// Summary of the lengths of the sequences in a file or assembly.
+#[derive(Debug, PartialEq, Clone)]
+pub struct LengthSummary {
+  pub records: usize,
+  pub total: u64,
+  pub min: u64,
+  pub max: u64,
+  pub mean: f64,
+  /// The length of the shortest sequence among the longest ones that together
+  /// hold at least half of all bases.
+  pub n50: u64,
+  /// As `n50`, for 90% of all bases.
+  pub n90: u64,
+  /// The number of sequences counted to reach the `n50`.
+  pub l50: usize,
+}
+
+impl LengthSummary {
+  pub fn new(lengths: &[u64]) -> LengthSummary {
+    let mut sorted = lengths.to_vec();
+    sorted.sort_unstable_by(|a, b| b.cmp(a));
+    let total: u64 = sorted.iter().sum();
+
+    // the count of longest sequences needed to hold `percent` of the bases
+    let lx = |percent: u64| {
+      let mut acc = 0;
+      sorted.iter()
+        .take_while(|l| {
+          let reached = acc * 100 >= total * percent;
+          acc += **l;
+          !reached
+        })
+        .count()
+    };
+    let nx = |percent: u64| match lx(percent) {
+      0 => 0,
+      n => sorted[n - 1],
+    };
+
+    LengthSummary {
+      records: sorted.len(),
+      total,
+      min: sorted.last().copied().unwrap_or(0),
+      max: sorted.first().copied().unwrap_or(0),
+      mean: if sorted.is_empty() { 0.0 } else { total as f64 / sorted.len() as f64 },
+      n50: nx(50),
+      n90: nx(90),
+      l50: lx(50),
+    }
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+
+  #[test]
+  fn test_composition() {
+    assert_eq!(gc_content("ACGTNNgc"), 4.0 / 6.0);
+    assert_eq!(gc_content("NNN"), 0.0);
+    assert_eq!(ambiguous_fraction("ACGTNRYA"), 3.0 / 8.0);
+    assert_eq!(ambiguous_fraction(""), 0.0);
+    assert_eq!(max_homopolymer("ACaaAAGTTT"), 4);
+    assert_eq!(max_homopolymer(""), 0);
+    assert_eq!(n_count("ANNnA"), 3);
+  }
+
+  #[test]
+  fn test_length_summary() {
+    let s = LengthSummary::new(&[2, 3, 4, 5, 6, 7, 8, 9, 10]);
+    assert_eq!((s.records, s.total, s.min, s.max, s.mean), (9, 54, 2, 10, 6.0));
+    assert_eq!((s.n50, s.l50), (8, 3));
+    assert_eq!(s.n90, 4);
+
+    let empty = LengthSummary::new(&[]);
+    assert_eq!((empty.records, empty.n50, empty.l50), (0, 0, 0));
+  }
+}
@@ -12,7 +12,7 @@ use clap::{
     crate_authors,
 };
 
-use chunks;
+type ChunkHandler = Box<dyn FnMut(&[u8])>;
 
 fn main() -> Result<(), io::Error> {
     let matches = App::new(crate_name!())
@@ -47,7 +47,7 @@ fn main() -> Result<(), io::Error> {
     };
 
     let commands = matches.values_of("commands");
-    let mut chunk_handler: Box<dyn FnMut(&[u8]) -> ()> = match commands {
+    let mut chunk_handler: ChunkHandler = match commands {
         None => Box::new(print_chunk),
         Some(mut cs) => {
             use std::process::*;
@@ -52,6 +52,8 @@ pub fn chunks<'a, R : Sized>(reader: R, delim: &'a Delim) -> Chunker<'a, R>
         buf: Vec::new(),
         from: 0,
         searched: 0,
+        buf_offset: 0,
+        chunk_offset: 0,
         done: false,
     }
 }
@@ -74,9 +76,19 @@ pub struct Chunker<'a, R> {
     buf: Vec<u8>,
     from: usize,
     searched: usize,
+    buf_offset: u64,
+    chunk_offset: u64,
     done: bool,
 }
 
+impl<R> Chunker<'_, R> {
+    /// Byte offset within the input of the start of the chunk most recently
+    /// returned by `next`.
+    pub fn offset(&self) -> u64 {
+        self.chunk_offset
+    }
+}
+
 impl<'a, R> Iterator for Chunker<'a, R>
 where
     R: BufRead,
@@ -110,11 +122,13 @@ where
                     match first_index_of(self.sentinel, &self.buf[self.searched..]) {
                         None => {
                             let cmp = compact_buf(self.from, &mut self.buf);
+                            self.buf_offset += cmp as u64;
 
                             match extend_buffer(&mut self.buf, &mut self.reader) {
                                 Ok(0) => {
                                     self.from = self.buf.len();
                                     self.done = true;
+                                    self.chunk_offset = self.buf_offset;
                                     return Some(Ok(self.buf[..].to_vec()));
                                 }
                                 Ok(_len) => {
@@ -138,6 +152,7 @@ where
                             };
 
                             let hit = &self.buf[self.from..hit_end];
+                            self.chunk_offset = self.buf_offset + self.from as u64;
                             self.from = hit_end;
                             self.searched = sentinel_end;
                             if !hit.is_empty()  { return Some(Ok(hit.to_vec())) }
@@ -271,6 +286,20 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_offsets() {
+        let delim = Delim::new(b"\n>", false);
+        let input: &[u8] = b">seq 1\nagct\n>seq2\ngattaca\n>seq3\n";
+        let mut ci = chunks(input, &delim);
+        let mut offsets = Vec::new();
+        while let Some(c) = ci.next() {
+            let c = c.expect("Failed to read chunk");
+            assert_eq!(&input[ci.offset() as usize..][..c.len()], &c[..]);
+            offsets.push(ci.offset());
+        }
+        assert_eq!(offsets, vec![0, 11, 25]);
+    }
+
     #[test]
     fn test_two_delimited_marks_end() {
         let delim = embl_delim();
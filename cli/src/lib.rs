@@ -0,0 +1,382 @@
+//! Command-line arguments shared between the tools, so that the same option
+//! means the same thing everywhere.
+
+use std::io;
+use std::str::FromStr;
+
+use clap::{
+    Arg,
+    ArgMatches,
+};
+
+use bio::alphabet::{
+    Alphabet,
+    InvalidResidue,
+    Policy,
+    Validator,
+};
+use bio::seq::fasta::{
+    Case,
+    FastaDescription,
+    FastaFormat,
+    FastaRecord,
+    LineEnding,
+};
+use bio::complexity::{
+    LowComplexity,
+    Method,
+};
+use bio::mask::MaskMode;
+use bio::seq::error::ParseError;
+use bio::seq::header::HeaderFields;
+use bio::seq::record::SeqError;
+
+/// Check that an argument parses as a `T`, for use as a clap validator.
+pub fn validate<T: FromStr>(v: String) -> Result<(), String>
+where
+    T::Err: ToString,
+{
+    T::from_str(&v).map(|_| ()).map_err(|e| e.to_string())
+}
+
+/// Check that an argument is a number between 0 and 1, for use as a clap
+/// validator.
+pub fn validate_fraction(v: String) -> Result<(), String> {
+    validate::<f64>(v.clone())?;
+    match v.parse::<f64>() {
+        Ok(f) if (0.0..=1.0).contains(&f) => Ok(()),
+        _ => Err(format!("`{}' is not between 0 and 1", v)),
+    }
+}
+
+/// Arguments controlling how FASTA output is written. Read them back with
+/// [fasta_format].
+pub fn fasta_format_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("lineLength")
+            .long("lineLength")
+            .multiple(false)
+            .takes_value(true)
+            .validator(validate::<usize>)
+            .help("Bases per line of FASTA output. Use 0 to write each sequence on one line. Defaults to 60."),
+        Arg::with_name("case")
+            .long("case")
+            .multiple(false)
+            .takes_value(true)
+            .possible_values(&["preserve", "upper", "lower"])
+            .help("Case of FASTA output sequences. Defaults to preserve."),
+        Arg::with_name("crlf")
+            .long("crlf")
+            .multiple(false)
+            .takes_value(false)
+            .help("End FASTA output lines with CRLF rather than LF."),
+        Arg::with_name("idOnly")
+            .long("idOnly")
+            .multiple(false)
+            .takes_value(false)
+            .help("Drop descriptions from FASTA output headers, keeping just the identifiers."),
+        Arg::with_name("idPrefix")
+            .long("idPrefix")
+            .multiple(false)
+            .takes_value(true)
+            .help("Prefix to add to the identifier of every FASTA output header."),
+    ]
+}
+
+/// Build the FASTA output format from the arguments in [fasta_format_args].
+pub fn fasta_format(matches: &ArgMatches) -> FastaFormat {
+    let mut format = FastaFormat::new();
+
+    if let Some(ll) = matches.value_of("lineLength") {
+        format = format.with_line_length(ll.parse().unwrap());
+    }
+    if let Some(case) = matches.value_of("case") {
+        format = format.with_case(Case::from_str(case).unwrap());
+    }
+    if matches.is_present("crlf") {
+        format = format.with_line_ending(LineEnding::CrLf);
+    }
+
+    let id_only = matches.is_present("idOnly");
+    let prefix = matches.value_of("idPrefix").map(String::from);
+    if id_only || prefix.is_some() {
+        format = format.with_header_rewrite(move |h| {
+            let fd = FastaDescription::read(h);
+            let id = format!("{}{}", prefix.as_deref().unwrap_or(""), fd.identifier.unwrap_or_default());
+            let descr = if id_only { None } else { fd.description };
+            FastaRecord::descr_line(Some(&id), descr.as_deref())
+        });
+    }
+
+    format
+}
+
+/// Argument to identify records by the accession parsed from their header,
+/// rather than by the raw first word. Read it back with [IdKey::from_matches].
+pub fn accession_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("accession")
+        .long("accession")
+        .multiple(false)
+        .takes_value(false)
+        .help("Identify records by the accession parsed from NCBI, UniProt or key=value style headers, rather than by the whole first word.")
+}
+
+/// How records are identified.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum IdKey {
+    /// The first word of the header.
+    Identifier,
+    /// The versioned accession parsed from the header.
+    Accession,
+}
+
+impl IdKey {
+    pub fn from_matches(matches: &ArgMatches) -> IdKey {
+        if matches.is_present("accession") { IdKey::Accession } else { IdKey::Identifier }
+    }
+
+    pub fn key(self, fd: &FastaDescription) -> Option<String> {
+        match self {
+            IdKey::Identifier => fd.identifier.clone(),
+            IdKey::Accession => HeaderFields::from_description(fd).versioned_accession()
+                .or_else(|| fd.identifier.clone()),
+        }
+    }
+}
+
+/// Arguments controlling how input sequences are checked against an alphabet.
+/// Read them back with [validator].
+pub fn validation_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("alphabet")
+            .long("alphabet")
+            .multiple(false)
+            .takes_value(true)
+            .possible_values(&["dna", "rna", "iupac", "protein"])
+            .help("Alphabet that input sequences are checked against. Defaults to iupac. Has no effect without --strict, --warn or --replace."),
+        Arg::with_name("strict")
+            .long("strict")
+            .multiple(false)
+            .takes_value(false)
+            .conflicts_with_all(&["warn", "replace"])
+            .help("Fail on the first residue that isn't in the alphabet, reporting its record, line and column."),
+        Arg::with_name("warn")
+            .long("warn")
+            .multiple(false)
+            .takes_value(false)
+            .conflicts_with_all(&["strict", "replace"])
+            .help("Report every residue that isn't in the alphabet on STDERR, and keep going."),
+        Arg::with_name("replace")
+            .long("replace")
+            .multiple(false)
+            .takes_value(true)
+            .conflicts_with_all(&["strict", "warn"])
+            .validator(|v| if v.len() == 1 && v.is_ascii() { Ok(()) } else {
+                Err(format!("Expected a single residue to replace with, but got `{}'", v))
+            })
+            .help("Replace every residue that isn't in the alphabet with this one."),
+    ]
+}
+
+/// Build the input validator from the arguments in [validation_args].
+///
+/// Returns `None` if no policy was chosen, in which case input isn't checked.
+pub fn validator(matches: &ArgMatches) -> Option<Validator> {
+    let policy = if matches.is_present("strict") {
+        Policy::Strict
+    } else if matches.is_present("warn") {
+        Policy::Warn
+    } else if let Some(r) = matches.value_of("replace") {
+        Policy::Replace(r.as_bytes()[0])
+    } else {
+        return None;
+    };
+    let alphabet = matches.value_of("alphabet")
+        .map(|a| Alphabet::from_str(a).unwrap())
+        .unwrap_or(Alphabet::IupacDna);
+
+    Some(Validator::new(alphabet, policy))
+}
+
+/// Names for each input given to `--seqIn` or similar, in the same order as
+/// `chunks::read_from_files_or_stdin` opens them.
+pub fn input_names<'a, I>(ins: Option<I>) -> Vec<String>
+where
+    I: Iterator<Item = &'a str>
+{
+    match ins {
+        Some(names) => names.map(String::from).collect(),
+        None => vec!["<stdin>".to_string()],
+    }
+}
+
+/// Argument choosing what to do with malformed records. Read it back with
+/// [ErrorPolicy::from_matches].
+pub fn error_policy_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("onError")
+        .long("onError")
+        .multiple(false)
+        .takes_value(true)
+        .possible_values(&["abort", "skip", "collect"])
+        .help("What to do with records that can't be parsed: abort on the first, skip each one with a message on STDERR, or collect them all and report them at the end with a failing exit status. Defaults to abort.")
+}
+
+/// What to do with records that can't be parsed.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ErrorPolicy {
+    Abort,
+    Skip,
+    Collect,
+}
+
+impl ErrorPolicy {
+    pub fn from_matches(matches: &ArgMatches) -> ErrorPolicy {
+        match matches.value_of("onError") {
+            Some("skip") => ErrorPolicy::Skip,
+            Some("collect") => ErrorPolicy::Collect,
+            _ => ErrorPolicy::Abort,
+        }
+    }
+}
+
+/// Applies an [ErrorPolicy] to parse errors as records are read.
+pub struct ErrorHandler {
+    policy: ErrorPolicy,
+    errors: Vec<ParseError>,
+}
+
+impl ErrorHandler {
+    pub fn new(policy: ErrorPolicy) -> ErrorHandler {
+        ErrorHandler { policy, errors: Vec::new() }
+    }
+
+    /// Deal with a parse error in the input `file`. Returns an error if
+    /// processing should stop.
+    pub fn handle(&mut self, file: &str, e: ParseError) -> io::Result<()> {
+        let e = e.with_file(file);
+        match self.policy {
+            ErrorPolicy::Abort => return Err(e.into()),
+            ErrorPolicy::Skip => eprintln!("Skipping record: {}", e),
+            ErrorPolicy::Collect => self.errors.push(e),
+        }
+        Ok(())
+    }
+
+    /// Report residues kept under `--warn` in the input `file` on STDERR.
+    pub fn warn(&self, file: &str, warnings: Vec<InvalidResidue>) {
+        for w in warnings {
+            eprintln!("Warning: {}: {}", file, w);
+        }
+    }
+
+    /// Pass a record through, or deal with its parse error and return `None`.
+    ///
+    /// Errors that reading can't carry on after, such as failing IO, are always
+    /// returned.
+    pub fn record<T>(&mut self, file: &str, r: Result<T, SeqError>) -> io::Result<Option<T>> {
+        match r {
+            Ok(r) => Ok(Some(r)),
+            Err(e) => {
+                self.handle(file, e.into_parse_error()?)?;
+                Ok(None)
+            }
+        }
+    }
+
+    /// Report any collected errors, failing if there were some.
+    pub fn finish(self) -> io::Result<()> {
+        for e in &self.errors {
+            eprintln!("{}", e);
+        }
+        match self.errors.len() {
+            0 => Ok(()),
+            n => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} records could not be parsed", n))),
+        }
+    }
+}
+
+/// Arguments controlling low-complexity detection and masking. Read them back
+/// with [low_complexity] and [low_complexity_mask].
+pub fn complexity_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("complexity")
+            .long("complexity")
+            .multiple(false)
+            .takes_value(true)
+            .possible_values(&["dust", "entropy"])
+            .help("How to find low-complexity regions: by DUST triplet score, or by Shannon entropy of the base composition. Defaults to dust."),
+        Arg::with_name("complexityWindow")
+            .long("complexityWindow")
+            .multiple(false)
+            .takes_value(true)
+            .validator(validate::<usize>)
+            .help("Window length for finding low-complexity regions. Defaults to 64."),
+        Arg::with_name("complexityThreshold")
+            .long("complexityThreshold")
+            .multiple(false)
+            .takes_value(true)
+            .validator(validate::<f64>)
+            .help("Windows with a DUST score above, or an entropy in bits below, this are low-complexity. Defaults to 20 for dust and 1 for entropy."),
+        Arg::with_name("maskLowComplexity")
+            .long("maskLowComplexity")
+            .multiple(false)
+            .takes_value(true)
+            .possible_values(&["soft", "hard"])
+            .help("Mask low-complexity regions with lower case (soft) or N (hard) before anything else is done with the sequence."),
+    ]
+}
+
+/// Build the low-complexity detector from the arguments in [complexity_args].
+pub fn low_complexity(matches: &ArgMatches) -> LowComplexity {
+    let mut lc = match matches.value_of("complexity").map(|m| Method::from_str(m).unwrap()) {
+        Some(Method::Entropy) => LowComplexity::entropy(),
+        _ => LowComplexity::dust(),
+    };
+    if let Some(w) = matches.value_of("complexityWindow") {
+        lc.window = w.parse().unwrap();
+    }
+    if let Some(t) = matches.value_of("complexityThreshold") {
+        lc.threshold = t.parse().unwrap();
+    }
+    lc
+}
+
+/// How to mask low-complexity regions, if at all, from [complexity_args].
+pub fn low_complexity_mask(matches: &ArgMatches) -> Option<MaskMode> {
+    matches.value_of("maskLowComplexity").map(|m| MaskMode::from_str(m).unwrap())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_validate_fraction() {
+        assert!(validate_fraction("0".to_string()).is_ok());
+        assert!(validate_fraction("0.25".to_string()).is_ok());
+        assert!(validate_fraction("1".to_string()).is_ok());
+        assert!(validate_fraction("1.5".to_string()).is_err());
+        assert!(validate_fraction("-0.1".to_string()).is_err());
+        assert!(validate_fraction("NaN".to_string()).is_err());
+        assert!(validate_fraction("abc".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_error_policies() {
+        let e = || Err::<(), _>(SeqError::Fasta(bio::seq::fasta::FastaError::Parse(ParseError::new(3, "bad"))));
+
+        let mut abort = ErrorHandler::new(ErrorPolicy::Abort);
+        assert!(abort.record("in.fa", e()).is_err());
+
+        let mut collect = ErrorHandler::new(ErrorPolicy::Collect);
+        assert_eq!(collect.record("in.fa", Ok(1)).unwrap(), Some(1));
+        assert_eq!(collect.record("in.fa", e()).unwrap(), None);
+        assert_eq!(collect.errors[0].to_string(), "in.fa:3: bad");
+        assert!(collect.finish().is_err());
+
+        let io = Err::<(), _>(SeqError::Fasta(bio::seq::fasta::FastaError::Io(io::ErrorKind::Other.into())));
+        assert!(ErrorHandler::new(ErrorPolicy::Skip).record("in.fa", io).is_err());
+    }
+}
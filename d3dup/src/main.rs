@@ -0,0 +1,158 @@
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::fs;
+use std::io::{
+    self,
+    BufRead,
+    Write,
+};
+
+use clap::{
+    Arg,
+    App,
+    crate_name,
+    crate_version,
+    crate_authors,
+};
+
+use bio::checksum::{normalise, sha512t24u};
+use bio::dna::try_reverse_complement;
+use bio::seq::record::SeqReader;
+
+fn main() -> Result<(), io::Error> {
+    let matches = App::new(crate_name!())
+        .version(crate_version!())
+        .author(crate_authors!())
+        .about("Collapses records with identical sequences")
+        .arg(Arg::with_name("seqIn")
+            .short("i")
+            .long("seqIn")
+            .multiple(true)
+            .takes_value(true)
+            .help("FASTA or FASTQ sequence input file. If not provided, defaults to STDIN."))
+        .arg(Arg::with_name("seqOut")
+            .short("o")
+            .long("seqOut")
+            .multiple(false)
+            .takes_value(true)
+            .help("Sequence output file, holding the first record with each sequence. If not provided, defaults to STDOUT."))
+        .arg(Arg::with_name("mapping")
+            .short("m")
+            .long("mapping")
+            .multiple(false)
+            .takes_value(true)
+            .help("Name of mapping file listing every original identifier and the identifier of the record representing it. Only generates mapping file if supplied."))
+        .arg(Arg::with_name("revComp")
+            .short("r")
+            .long("revComp")
+            .multiple(false)
+            .takes_value(false)
+            .help("Treat a sequence and its reverse complement as the same."))
+        .arg(cli::accession_arg())
+        .args(&cli::fasta_format_args())
+        .args(&cli::validation_args())
+        .arg(cli::error_policy_arg())
+        .get_matches();
+
+    let mut out =
+        chunks::write_to_file_or_stdout(matches.value_of("seqOut"))?;
+
+    let ins: Vec<Box<dyn BufRead>> =
+        chunks::read_from_files_or_stdin(matches.values_of("seqIn"))?;
+
+    let mut mapping = match matches.value_of("mapping") {
+        Some(m) => Some(fs::File::create(m)?),
+        None => None,
+    };
+
+    let rev_comp = matches.is_present("revComp");
+    let fasta = cli::fasta_format(&matches);
+    let id_key = cli::IdKey::from_matches(&matches);
+    let validator = cli::validator(&matches);
+    let names = cli::input_names(matches.values_of("seqIn"));
+    let mut errors = cli::ErrorHandler::new(cli::ErrorPolicy::from_matches(&matches));
+
+    // only digests and ids are kept, so memory doesn't grow with sequence length
+    let mut representatives: HashMap<String, String> = HashMap::new();
+    let mut count = 0;
+    for (name, in_reader) in names.iter().zip(ins) {
+        let mut reader = SeqReader::new(in_reader)?.with_validator(validator);
+        while let Some(in_seq) = reader.next() {
+            errors.warn(name, reader.take_warnings());
+            let in_seq = match errors.record(name, in_seq)? {
+                Some(s) => s,
+                None => continue,
+            };
+            count += 1;
+            let id = id_key.key(&in_seq.description()).unwrap_or_else(|| count.to_string());
+
+            let representative = match representatives.entry(sequence_key(in_seq.seq(), rev_comp)) {
+                Entry::Occupied(e) => e.get().clone(),
+                Entry::Vacant(e) => {
+                    in_seq.write(&fasta, &mut out)?;
+                    e.insert(id.clone()).clone()
+                }
+            };
+            if let Some(m) = mapping.as_mut() {
+                writeln!(m, "{}\t{}", id, representative)?;
+            }
+        }
+    }
+
+    errors.finish()
+}
+
+/// Digest of the sequence, ignoring case and, if `rev_comp` is set, strand.
+///
+/// Sequences holding anything other than nucleotides, such as proteins or
+/// alignment gaps, have no reverse complement and are keyed on the forward
+/// strand only.
+fn sequence_key(seq: &str, rev_comp: bool) -> String {
+    let forward = normalise(seq.as_bytes());
+    if rev_comp {
+        if let Ok(reverse) = try_reverse_complement(&String::from_utf8_lossy(&forward)) {
+            if reverse.as_bytes() < &forward[..] {
+                return sha512t24u(reverse.as_bytes());
+            }
+        }
+    }
+    sha512t24u(&forward)
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_sequence_key_ignores_case() {
+        assert_eq!(sequence_key("ACGTTA", false), sequence_key("acgtta", false));
+        assert_eq!(sequence_key("ACGTTA", true), sequence_key("AcGttA", true));
+        assert_ne!(sequence_key("ACGTTA", false), sequence_key("ACGTTT", false));
+    }
+
+    #[test]
+    fn test_sequence_key_reverse_complement() {
+        // TAACGT is the reverse complement of ACGTTA
+        assert_eq!(sequence_key("ACGTTA", true), sequence_key("TAACGT", true));
+        assert_eq!(sequence_key("ACGTTA", true), sequence_key("taacgt", true));
+        assert_ne!(sequence_key("ACGTTA", false), sequence_key("TAACGT", false));
+        assert_ne!(sequence_key("ACGTTA", true), sequence_key("ACGTTT", true));
+    }
+
+    #[test]
+    fn test_sequence_key_protein() {
+        // both would reverse complement to NNNN
+        assert_ne!(sequence_key("MKLV", true), sequence_key("MKIV", true));
+        assert_eq!(sequence_key("MKLV", true), sequence_key("MKLV", false));
+        assert_eq!(sequence_key("MKLV", true), sequence_key("mklv", true));
+    }
+
+    #[test]
+    fn test_sequence_key_gaps() {
+        // AC-GTT is the reverse complement of AAC-GT, bar the gap
+        assert_ne!(sequence_key("AAC-GT", true), sequence_key("AAC.GT", true));
+        assert_ne!(sequence_key("AAC-GT", true), sequence_key("AC-GTT", true));
+        assert_eq!(sequence_key("AAC-GT", true), sequence_key("AAC-GT", false));
+    }
+}
@@ -0,0 +1,105 @@
+use std::io::{
+    self,
+    BufRead,
+    BufReader,
+};
+use std::fs::File;
+
+use clap::{
+    Arg,
+    App,
+    crate_name,
+    crate_version,
+    crate_authors,
+};
+
+use bio::{
+    dna::reverse_complement,
+    seq::fasta::*,
+};
+
+fn main() -> Result<(), io::Error> {
+    let matches = App::new(crate_name!())
+        .version(crate_version!())
+        .author(crate_authors!())
+        .about("Fetches regions of sequences from an indexed fasta file")
+        .arg(Arg::with_name("seqIn")
+            .short("i")
+            .long("seqIn")
+            .multiple(false)
+            .takes_value(true)
+            .required(true)
+            .help("FASTA sequence input file. This must be a file rather than STDIN, so that it can be seeked."))
+        .arg(Arg::with_name("fai")
+            .long("fai")
+            .multiple(false)
+            .takes_value(true)
+            .help("Index file. Defaults to the input file name with `.fai` appended. If the index doesn't exist, it is created."))
+        .arg(Arg::with_name("seqOut")
+            .short("o")
+            .long("seqOut")
+            .multiple(false)
+            .takes_value(true)
+            .help("Sequence output file. If not provided, defaults to STDOUT."))
+        .arg(Arg::with_name("regionFile")
+            .short("r")
+            .long("regionFile")
+            .multiple(false)
+            .takes_value(true)
+            .help("File listing regions to fetch, one per line."))
+        .arg(Arg::with_name("revComp")
+            .long("revComp")
+            .multiple(false)
+            .takes_value(false)
+            .help("Reverse complement the fetched regions."))
+        .arg(Arg::with_name("regions")
+            .multiple(true)
+            .help("Regions to fetch, as `id`, `id:start` or `id:start-end`, with 1-based inclusive coordinates."))
+        .args(&cli::fasta_format_args())
+        .get_matches();
+
+    let mut out =
+        chunks::write_to_file_or_stdout(matches.value_of("seqOut"))?;
+
+    let seq_in = matches.value_of("seqIn").unwrap();
+    let fai_name = matches.value_of("fai")
+        .map(String::from)
+        .unwrap_or_else(|| format!("{}.fai", seq_in));
+
+    let index = match File::open(&fai_name) {
+        Ok(f) => FastaIndex::read(BufReader::new(f))?,
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => {
+            let index = FastaIndex::build(BufReader::new(File::open(seq_in)?))?;
+            index.write(&mut File::create(&fai_name)?)?;
+            index
+        }
+        Err(e) => return Err(e),
+    };
+
+    let mut regions: Vec<String> = matches.values_of("regions")
+        .map(|rs| rs.map(String::from).collect())
+        .unwrap_or_default();
+    if let Some(region_file) = matches.value_of("regionFile") {
+        for line in BufReader::new(File::open(region_file)?).lines() {
+            let line = line?;
+            if !line.trim().is_empty() { regions.push(line) }
+        }
+    }
+
+    let rev_comp = matches.is_present("revComp");
+    let mut seqs = File::open(seq_in)?;
+    let fasta = cli::fasta_format(&matches);
+    for r in regions {
+        let region = Region::parse(&r, &index)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let seq = index.fetch(&mut seqs, &region)?;
+        let rec = if rev_comp {
+            FastaRecord { descr_line: format!("{}/rc", region), seq: reverse_complement(&seq) }
+        } else {
+            FastaRecord { descr_line: region.to_string(), seq }
+        };
+        rec.write(&fasta, &mut out)?;
+    }
+
+    Ok(())
+}
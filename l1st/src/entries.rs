@@ -0,0 +1,229 @@
+//! Identifiers of the entries in EMBL, GenBank and GFF3 files. EMBL entries
+//! are parsed with [EmblReader], while GenBank and GFF3 files are only read as
+//! far as their ids.
+
+use std::collections::HashSet;
+use std::io::{self, BufRead};
+
+use bio::seq::embl::{Embl, EmblReader};
+use bio::seq::error::{ParseError, ReadError};
+use bio::seq::record::FileFormat;
+
+/// The ways an entry may be named.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct EntryIds {
+    /// The EMBL `ID` or GenBank `LOCUS` name, or the GFF3 seqid.
+    pub name: Option<String>,
+    /// The primary accession, with its version where one is given.
+    pub accession: Option<String>,
+}
+
+/// Errors raised while listing entry ids, shared with the sequence readers.
+pub type EntryError = ReadError;
+
+impl From<&Embl> for EntryIds {
+    fn from(e: &Embl) -> Self {
+        let accession = e.accession().map(|a| match e.version() {
+            Some(v) if !a.contains('.') => format!("{}.{}", a, v),
+            _ => a.to_string(),
+        });
+        EntryIds { name: e.id().map(ToString::to_string), accession }
+    }
+}
+
+/// Where an [EntryIdReader] reads from.
+enum Source<R> {
+    Lines(R),
+    Embl(EmblReader<R>),
+}
+
+/// Reads the ids of each EMBL or GenBank entry, or the distinct seqids of a
+/// GFF3 file in the order they first appear.
+pub struct EntryIdReader<R> {
+    source: Source<R>,
+    format: FileFormat,
+    line: String,
+    line_no: usize,
+    record: usize,
+    seen: HashSet<String>,
+    done: bool,
+}
+
+impl <R : BufRead> EntryIdReader<R> {
+    /// Panics if `format` is FASTA or FASTQ, which are read as sequence
+    /// records instead.
+    pub fn new(reader: R, format: FileFormat) -> EntryIdReader<R> {
+        assert!(format.seq_format().is_none(), "{:?} entries are read as sequence records", format);
+        let source = match format {
+            FileFormat::Embl => Source::Embl(EmblReader::new(reader)),
+            _ => Source::Lines(reader),
+        };
+        EntryIdReader { source, format, line: String::new(), line_no: 0, record: 0, seen: HashSet::new(), done: false }
+    }
+
+    fn next_line(&mut self) -> io::Result<bool> {
+        self.line.clear();
+        let reader = match &mut self.source {
+            Source::Lines(r) => r,
+            Source::Embl(_) => return Ok(false),
+        };
+        let mut bytes = Vec::new();
+        if reader.read_until(b'\n', &mut bytes)? == 0 {
+            return Ok(false)
+        }
+        self.line_no += 1;
+        self.line.push_str(String::from_utf8_lossy(&bytes).trim_end());
+        Ok(true)
+    }
+
+    fn next_embl(&mut self) -> Result<Option<EntryIds>, EntryError> {
+        match &mut self.source {
+            Source::Embl(r) => r.next().transpose()
+                .map(|e| e.as_ref().map(EntryIds::from)),
+            Source::Lines(_) => Ok(None),
+        }
+    }
+
+    /// Read one `//` terminated GenBank entry.
+    fn next_genbank(&mut self) -> Result<Option<EntryIds>, EntryError> {
+        let mut ids = EntryIds::default();
+        let mut start = None;
+        while self.next_line()? {
+            let line = self.line.as_str();
+            if line == "//" {
+                break
+            }
+            if line.trim().is_empty() {
+                continue
+            }
+            start.get_or_insert(self.line_no);
+            let mut words = line.split_whitespace();
+            match words.next() {
+                Some("LOCUS") => {
+                    ids.name = words.next().map(ToString::to_string);
+                }
+                Some("ACCESSION") if ids.accession.is_none() => {
+                    ids.accession = words.next().map(ToString::to_string);
+                }
+                Some("VERSION") => {
+                    if let Some(v) = words.next() {
+                        ids.accession = Some(v.to_string());
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        let start = match start {
+            Some(start) => start,
+            None => return Ok(None),
+        };
+        self.record += 1;
+        if ids.name.is_none() && ids.accession.is_none() {
+            return Err(EntryError::Parse(ParseError::new(start, "entry has no LOCUS, ACCESSION or VERSION line")
+                .with_record(self.record)))
+        }
+        Ok(Some(ids))
+    }
+
+    /// Read up to the next seqid not seen before.
+    fn next_gff_seq_id(&mut self) -> Result<Option<EntryIds>, EntryError> {
+        while self.next_line()? {
+            if self.line.starts_with("##FASTA") {
+                return Ok(None)
+            }
+            if self.line.trim().is_empty() || self.line.starts_with('#') {
+                continue
+            }
+            self.record += 1;
+            let columns = self.line.split('\t').count();
+            if columns != 9 {
+                return Err(EntryError::Parse(
+                    ParseError::new(self.line_no, format!("expected 9 tab-separated columns, but found {}", columns))
+                        .with_record(self.record)))
+            }
+            let seq_id = self.line.split('\t').next().unwrap();
+            if !self.seen.contains(seq_id) {
+                self.seen.insert(seq_id.to_string());
+                return Ok(Some(EntryIds { name: Some(seq_id.to_string()), accession: None }))
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl <R : BufRead> Iterator for EntryIdReader<R> {
+    type Item = Result<EntryIds, EntryError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done { return None }
+        let next = match self.format {
+            FileFormat::Gff3 => self.next_gff_seq_id(),
+            FileFormat::Embl => self.next_embl(),
+            _ => self.next_genbank(),
+        };
+        match next {
+            Ok(None) => { self.done = true; None }
+            Ok(Some(ids)) => Some(Ok(ids)),
+            Err(EntryError::Io(e)) => { self.done = true; Some(Err(EntryError::Io(e))) }
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_embl_ids() {
+        let input = "ID   X56734; SV 1; linear; mRNA; STD; PLN; 1859 BP.\nXX\nAC   X56734; S46826;\nXX\n\
+            SQ   Sequence 4 BP;\n     acgt                                                               4\n//\n\
+            ID   XXX; SV 2; linear; DNA; STD; PLN; 4 BP.\n//\n\
+            DE   nothing to name this by\n//\n\
+            ID   Y00001; linear; DNA; STD; PLN; 4 BP.\nAC   Y00001;\n//\n\
+            ID   TRBG361    standard; RNA; PLN; 1859 BP.\nAC   X56734;\n//\n";
+        let ids: Vec<_> = EntryIdReader::new(input.as_bytes(), FileFormat::Embl).collect();
+        assert_eq!(ids.len(), 5);
+        assert_eq!(ids[0].as_ref().unwrap(),
+            &EntryIds { name: Some("X56734".to_string()), accession: Some("X56734.1".to_string()) });
+        assert_eq!(ids[1].as_ref().unwrap(), &EntryIds { name: Some("XXX".to_string()), accession: None });
+        match &ids[2] {
+            Err(EntryError::Parse(e)) => assert_eq!((e.line, e.record), (10, Some(3))),
+            other => panic!("Expected a parse error but got {:?}", other),
+        }
+        assert_eq!(ids[3].as_ref().unwrap().accession.as_deref(), Some("Y00001"));
+        assert_eq!(ids[4].as_ref().unwrap(),
+            &EntryIds { name: Some("TRBG361".to_string()), accession: Some("X56734".to_string()) });
+    }
+
+    #[test]
+    fn test_genbank_ids() {
+        let input = "LOCUS       SCU49845     5028 bp    DNA             PLN       21-JUN-1999\n\
+            ACCESSION   U49845 U49846\nVERSION     U49845.1  GI:1293613\nORIGIN\n        1 gatcctccat\n//\n\
+            LOCUS       AB000001     10 bp    DNA\nACCESSION   AB000001\n//\n";
+        let ids: Vec<_> = EntryIdReader::new(input.as_bytes(), FileFormat::GenBank)
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(ids, vec![
+            EntryIds { name: Some("SCU49845".to_string()), accession: Some("U49845.1".to_string()) },
+            EntryIds { name: Some("AB000001".to_string()), accession: Some("AB000001".to_string()) },
+        ]);
+    }
+
+    #[test]
+    fn test_gff_seq_ids() {
+        let input = "##gff-version 3\nctg2\t.\tgene\t1\t10\t.\t+\t.\tID=g1\n# comment\n\
+            ctg1\t.\tgene\t1\t10\t.\t+\t.\tID=g2\nctg2\t.\tmRNA\t1\t10\t.\t+\t.\tParent=g1\n\
+            ctg3\tbroken\n##FASTA\n>ctg4\nACGT\n";
+        let ids: Vec<_> = EntryIdReader::new(input.as_bytes(), FileFormat::Gff3).collect();
+        assert_eq!(ids.len(), 3);
+        assert_eq!(ids[0].as_ref().unwrap().name.as_deref(), Some("ctg2"));
+        assert_eq!(ids[1].as_ref().unwrap().name.as_deref(), Some("ctg1"));
+        match &ids[2] {
+            Err(EntryError::Parse(e)) => assert_eq!((e.line, e.record), (6, Some(4))),
+            other => panic!("Expected a parse error but got {:?}", other),
+        }
+    }
+}
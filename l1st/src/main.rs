@@ -6,9 +6,21 @@ use clap::{
     crate_authors,
 };
 
-use std::io;
+use std::fs::File;
+use std::io::{
+    self,
+    Write,
+};
+use std::str::FromStr;
+
+use bio::checksum::Checksum;
+use bio::mask::soft_masked_fraction;
+use bio::stats::{gc_content, n_count, LengthSummary};
 
-use bio::seq::fasta::*;
+use bio::seq::record::{FileFormat, SeqReader};
+
+mod entries;
+use entries::{EntryError, EntryIdReader};
 
 
 fn main() -> Result<(), io::Error> {
@@ -19,42 +31,182 @@ fn main() -> Result<(), io::Error> {
         .arg(Arg::with_name("seqIn")
             .short("i")
             .long("seqIn")
-            .multiple(false)
+            .multiple(true)
             .takes_value(true)
-            .help("Fasta-formatted sequence input file. If not provided, defaults to STDIN.")
+            .help("FASTA, FASTQ, EMBL, GenBank or GFF3 input file. If not provided, defaults to STDIN.")
         )
+        .arg(Arg::with_name("type")
+            .long("type")
+            .multiple(false)
+            .takes_value(true)
+            .possible_values(&["fasta", "fastq", "embl", "genbank", "gff3"])
+            .help("Input format. If not provided, it is detected from the start of each input. EMBL and GenBank entries are listed by their ID or LOCUS name, or with --accession by their versioned accession. GFF3 files list each distinct seqid."))
         .arg(Arg::with_name("idsOut")
             .short("o")
             .long("idsOut")
             .multiple(false)
             .takes_value(true)
             .help("Id list output file. If not provided, defaults to STDOUT."))
+        .arg(Arg::with_name("checksum")
+            .short("c")
+            .long("checksum")
+            .multiple(true)
+            .takes_value(true)
+            .use_delimiter(true)
+            .possible_values(&["crc64", "md5", "seguid", "sha512t24u", "refget"])
+            .help("Checksums of each sequence to write in tab-separated columns after its ID, ignoring case and line wrapping. May be given more than once, or as a comma-separated list."))
+        .arg(Arg::with_name("stats")
+            .short("s")
+            .long("stats")
+            .multiple(false)
+            .takes_value(false)
+            .help("Write a table of id, length, GC percentage, N count, soft-masked fraction, any checksums and the description of each record, rather than just the ids."))
+        .arg(Arg::with_name("summary")
+            .long("summary")
+            .multiple(false)
+            .takes_value(true)
+            .help("File to write a summary of each input to: number of records, total, min, max and mean length, N50, N90 and L50."))
+        .arg(Arg::with_name("json")
+            .long("json")
+            .multiple(false)
+            .takes_value(false)
+            .help("Write the stats table and summary as JSON, one object per line, rather than TSV."))
+        .arg(cli::accession_arg())
+        .args(&cli::validation_args())
+        .arg(cli::error_policy_arg())
         .get_matches();
     
     let mut out =
-        chunks::write_to_file_or_stdout(matches.value_of("seqOut"))
+        chunks::write_to_file_or_stdout(matches.value_of("idsOut"))
         .expect("Failed to open output file for writing");
 
     let ins = chunks::read_from_files_or_stdin(matches.values_of("seqIn"))
         .expect("Failed to open input file for reading");
 
-    let delim = chunks::Delim::new(b">", false);
-
-    for in_reader in ins {
-        for chunk in chunks::chunks(in_reader, &delim) {
-            let chunk = chunk.expect("Failed to read chunk");
-            let chunk_text = std::str::from_utf8(&chunk).unwrap();
-            match parse_fastas(&chunk_text) {
-                Ok((_, in_seqs)) => for in_seq in in_seqs {
-                    let fd = FastaDescription::read(&in_seq.descr_line);
-                    if let Some(id) = fd.identifier {
-                        writeln!(out, "{}", id)?
+    let id_key = cli::IdKey::from_matches(&matches);
+    let checksums: Vec<Checksum> = matches.values_of("checksum")
+        .map(|cs| cs.map(|c| Checksum::from_str(c).unwrap()).collect())
+        .unwrap_or_default();
+    let stats = matches.is_present("stats");
+    let json = matches.is_present("json");
+    let mut summary = match matches.value_of("summary") {
+        Some(f) => Some(File::create(f)?),
+        None => None,
+    };
+    if stats && !json {
+        write!(out, "id\tlength\tgc\tn\tmasked")?;
+        for c in &checksums {
+            write!(out, "\t{}", c.name())?;
+        }
+        writeln!(out, "\tdescription")?;
+    }
+    if let (Some(sm), false) = (summary.as_mut(), json) {
+        writeln!(sm, "file\trecords\ttotal\tmin\tmax\tmean\tn50\tn90\tl50")?;
+    }
+
+    let in_type = matches.value_of("type").map(|t| FileFormat::from_str(t).unwrap());
+    let validator = cli::validator(&matches);
+    let names = cli::input_names(matches.values_of("seqIn"));
+    let mut errors = cli::ErrorHandler::new(cli::ErrorPolicy::from_matches(&matches));
+    for (name, mut in_reader) in names.iter().zip(ins) {
+        let format = match in_type {
+            Some(t) => t,
+            None => FileFormat::detect(&mut in_reader)?.unwrap_or(FileFormat::Fasta),
+        };
+        let seq_format = match format.seq_format() {
+            Some(f) => f,
+            None => {
+                if stats || summary.is_some() || !checksums.is_empty() {
+                    return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                        format!("{}: --stats, --summary and --checksum need FASTA or FASTQ input, but this is {:?}", name, format)));
+                }
+                for ids in EntryIdReader::new(in_reader, format) {
+                    let ids = match ids {
+                        Ok(ids) => ids,
+                        Err(EntryError::Parse(e)) => { errors.handle(name, e)?; continue }
+                        Err(e) => return Err(e.into()),
+                    };
+                    let id = match id_key {
+                        cli::IdKey::Identifier => ids.name.or(ids.accession),
+                        cli::IdKey::Accession => ids.accession.or(ids.name),
+                    };
+                    if let Some(id) = id {
+                        writeln!(out, "{}", id)?;
+                    }
+                }
+                continue
+            }
+        };
+
+        let mut lengths = Vec::new();
+        let mut reader = SeqReader::with_format(in_reader, seq_format).with_validator(validator);
+        while let Some(in_seq) = reader.next() {
+            errors.warn(name, reader.take_warnings());
+            let in_seq = match errors.record(name, in_seq)? {
+                Some(s) => s,
+                None => continue,
+            };
+            let seq = in_seq.seq();
+            lengths.push(seq.len() as u64);
+            let fd = in_seq.description();
+            if let Some(id) = id_key.key(&fd) {
+                let digests: Vec<(&str, String)> = checksums.iter()
+                    .map(|c| (c.name(), c.digest(seq.as_bytes())))
+                    .collect();
+                if !stats {
+                    write!(out, "{}", id)?;
+                    for (_, d) in &digests {
+                        write!(out, "\t{}", d)?;
+                    }
+                    writeln!(out)?
+                } else if json {
+                    write!(out, "{{\"id\": {}, \"length\": {}, \"gc\": {:.2}, \"n\": {}, \"masked\": {:.4}",
+                        json_string(&id), seq.len(), gc_content(seq) * 100.0, n_count(seq), soft_masked_fraction(seq))?;
+                    for (c, d) in &digests {
+                        write!(out, ", \"{}\": {}", c, json_string(d))?;
+                    }
+                    match &fd.description {
+                        Some(d) => writeln!(out, ", \"description\": {}}}", json_string(d))?,
+                        None => writeln!(out, ", \"description\": null}}")?,
+                    }
+                } else {
+                    write!(out, "{}\t{}\t{:.2}\t{}\t{:.4}",
+                        id, seq.len(), gc_content(seq) * 100.0, n_count(seq), soft_masked_fraction(seq))?;
+                    for (_, d) in &digests {
+                        write!(out, "\t{}", d)?;
                     }
-                },
-                Err(e) => println!("Error parsing fasta input:\n{:#?}\n", e)
+                    writeln!(out, "\t{}", fd.description.as_deref().unwrap_or(""))?
+                }
+            }
+        }
+
+        if let Some(sm) = summary.as_mut() {
+            let s = LengthSummary::new(&lengths);
+            if json {
+                writeln!(sm, "{{\"file\": {}, \"records\": {}, \"total\": {}, \"min\": {}, \"max\": {}, \"mean\": {:.2}, \"n50\": {}, \"n90\": {}, \"l50\": {}}}",
+                    json_string(name), s.records, s.total, s.min, s.max, s.mean, s.n50, s.n90, s.l50)?;
+            } else {
+                writeln!(sm, "{}\t{}\t{}\t{}\t{}\t{:.2}\t{}\t{}\t{}",
+                    name, s.records, s.total, s.min, s.max, s.mean, s.n50, s.n90, s.l50)?;
             }
         }
     }
 
-    Ok(())
+    errors.finish()
+}
+
+/// Quote and escape text as a JSON string.
+fn json_string(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
 }
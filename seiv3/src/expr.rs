@@ -0,0 +1,378 @@
+//! A small expression language for choosing records, such as
+//! `len > 500 && gc < 0.6 && !id ~ "^scaffold"`.
+//!
+//! Numeric fields are compared with `<`, `<=`, `>`, `>=`, `==` and `!=`:
+//!
+//!   * `len`: sequence length
+//!   * `gc`: fraction of G and C among the unambiguous bases
+//!   * `ambig`: fraction of bases that aren't ACGTU
+//!   * `homopolymer`: length of the longest single-residue run
+//!   * `masked`: fraction of soft-masked (lower case) bases
+//!
+//! Text fields are compared with `==` and `!=`, or matched against a regular
+//! expression with `~` and `!~`:
+//!
+//!   * `id`: the identifier
+//!   * `desc`: the description, or the empty string if there is none
+//!   * `seq`: the sequence
+//!
+//! Numbers may be negative and use exponents, as in `-1.5` or `1e-3`. In
+//! quoted strings, `\"` and `\\` stand for a quote and a backslash, and any
+//! other backslash is kept, so `id ~ "\d+"` matches digits.
+//!
+//! Terms combine with `!`, `&&` and `||`, in that order of precedence, and can
+//! be grouped with parentheses.
+
+use std::str::FromStr;
+
+use regex::Regex;
+
+use bio::mask::soft_masked_fraction;
+use bio::seq::record::SeqRecord;
+use bio::stats::{ambiguous_fraction, gc_content, max_homopolymer};
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum NumField {
+    Len,
+    Gc,
+    Ambig,
+    Homopolymer,
+    Masked,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum TextField {
+    Id,
+    Desc,
+    Seq,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum CmpOp {
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+}
+
+#[derive(Debug)]
+pub enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Compare(NumField, CmpOp, f64),
+    Equals(TextField, String),
+    Matches(TextField, Regex),
+}
+
+impl Expr {
+    /// True if the record is chosen by this expression.
+    pub fn eval(&self, rec: &SeqRecord) -> bool {
+        match self {
+            Expr::And(l, r) => l.eval(rec) && r.eval(rec),
+            Expr::Or(l, r) => l.eval(rec) || r.eval(rec),
+            Expr::Not(e) => !e.eval(rec),
+            Expr::Compare(f, op, v) => {
+                let x = f.value(rec);
+                match op {
+                    CmpOp::Lt => x < *v,
+                    CmpOp::Le => x <= *v,
+                    CmpOp::Gt => x > *v,
+                    CmpOp::Ge => x >= *v,
+                    CmpOp::Eq => x == *v,
+                    CmpOp::Ne => x != *v,
+                }
+            }
+            Expr::Equals(f, s) => f.value(rec) == *s,
+            Expr::Matches(f, re) => re.is_match(&f.value(rec)),
+        }
+    }
+}
+
+impl NumField {
+    fn value(self, rec: &SeqRecord) -> f64 {
+        let seq = rec.seq();
+        match self {
+            NumField::Len => seq.len() as f64,
+            NumField::Gc => gc_content(seq),
+            NumField::Ambig => ambiguous_fraction(seq),
+            NumField::Homopolymer => max_homopolymer(seq) as f64,
+            NumField::Masked => soft_masked_fraction(seq),
+        }
+    }
+}
+
+impl TextField {
+    fn value(self, rec: &SeqRecord) -> String {
+        match self {
+            TextField::Id => rec.description().identifier.unwrap_or_default(),
+            TextField::Desc => rec.description().description.unwrap_or_default(),
+            TextField::Seq => rec.seq().to_string(),
+        }
+    }
+}
+
+impl FromStr for Expr {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let tokens = tokenise(s)?;
+        let mut parser = Parser { tokens: &tokens, at: 0, end: s.len() };
+        let expr = parser.or()?;
+        match parser.peek() {
+            None => Ok(expr),
+            Some((pos, t)) => Err(format!("Unexpected {} at column {}", t, pos + 1)),
+        }
+    }
+}
+
+
+#[derive(Debug, PartialEq, Clone)]
+enum Token {
+    Ident(String),
+    Number(f64),
+    Str(String),
+    Op(&'static str),
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Ident(i) => write!(f, "`{}'", i),
+            Token::Number(n) => write!(f, "`{}'", n),
+            Token::Str(s) => write!(f, "\"{}\"", s),
+            Token::Op(o) => write!(f, "`{}'", o),
+        }
+    }
+}
+
+/// Operators, longest first so that `<=` isn't read as `<`.
+const OPS: &[&str] = &["&&", "||", "<=", ">=", "==", "!=", "!~", "<", ">", "!", "~", "(", ")"];
+
+fn tokenise(s: &str) -> Result<Vec<(usize, Token)>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = s.char_indices().peekable();
+    while let Some(&(i, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '"' {
+            chars.next();
+            let mut text = String::new();
+            loop {
+                match chars.next() {
+                    Some((_, '"')) => break,
+                    // only quotes and backslashes are escaped, so regular
+                    // expressions keep escapes such as `\d`
+                    Some((_, '\\')) => match chars.next() {
+                        Some((_, e)) if e == '"' || e == '\\' => text.push(e),
+                        Some((_, e)) => {
+                            text.push('\\');
+                            text.push(e);
+                        }
+                        None => return Err(format!("Unterminated string at column {}", i + 1)),
+                    },
+                    Some((_, c)) => text.push(c),
+                    None => return Err(format!("Unterminated string at column {}", i + 1)),
+                }
+            }
+            tokens.push((i, Token::Str(text)));
+        } else if c.is_ascii_digit() || c == '.'
+            || (c == '-' && s[i + 1..].starts_with(|d: char| d.is_ascii_digit() || d == '.')) {
+            chars.next();
+            let mut end = i + 1;
+            while let Some(&(j, d)) = chars.peek() {
+                let exponent_sign = (d == '-' || d == '+') && s[..j].ends_with(['e', 'E']);
+                if !(d.is_ascii_digit() || d == '.' || d == 'e' || d == 'E' || exponent_sign) { break }
+                end = j + d.len_utf8();
+                chars.next();
+            }
+            let n = f64::from_str(&s[i..end])
+                .map_err(|e| format!("Bad number `{}' at column {}: {}", &s[i..end], i + 1, e))?;
+            tokens.push((i, Token::Number(n)));
+        } else if c.is_ascii_alphabetic() {
+            let mut end = i;
+            while let Some(&(j, d)) = chars.peek() {
+                if !(d.is_ascii_alphanumeric() || d == '_') { break }
+                end = j + 1;
+                chars.next();
+            }
+            tokens.push((i, Token::Ident(s[i..end].to_string())));
+        } else {
+            match OPS.iter().find(|op| s[i..].starts_with(*op)) {
+                Some(op) => {
+                    for _ in 0..op.len() { chars.next(); }
+                    tokens.push((i, Token::Op(op)));
+                }
+                None => return Err(format!("Unexpected `{}' at column {}", c, i + 1)),
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser<'t> {
+    tokens: &'t [(usize, Token)],
+    at: usize,
+    /// Length of the input, to report errors at its end.
+    end: usize,
+}
+
+impl <'t> Parser<'t> {
+    fn peek(&self) -> Option<&'t (usize, Token)> {
+        self.tokens.get(self.at)
+    }
+
+    fn next(&mut self) -> Result<&'t (usize, Token), String> {
+        let t = self.tokens.get(self.at)
+            .ok_or_else(|| format!("Unexpected end of expression at column {}", self.end + 1))?;
+        self.at += 1;
+        Ok(t)
+    }
+
+    fn eat(&mut self, op: &str) -> bool {
+        match self.peek() {
+            Some((_, Token::Op(o))) if *o == op => { self.at += 1; true }
+            _ => false,
+        }
+    }
+
+    fn or(&mut self) -> Result<Expr, String> {
+        let mut e = self.and()?;
+        while self.eat("||") {
+            e = Expr::Or(Box::new(e), Box::new(self.and()?));
+        }
+        Ok(e)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut e = self.unary()?;
+        while self.eat("&&") {
+            e = Expr::And(Box::new(e), Box::new(self.unary()?));
+        }
+        Ok(e)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.eat("!") {
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        if self.eat("(") {
+            let e = self.or()?;
+            return match self.next()? {
+                (_, Token::Op(")")) => Ok(e),
+                (pos, t) => Err(format!("Expected `)' but found {} at column {}", t, pos + 1)),
+            };
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> Result<Expr, String> {
+        let (pos, field) = match self.next()? {
+            (pos, Token::Ident(f)) => (*pos, f.as_str()),
+            (pos, t) => return Err(format!("Expected a field name but found {} at column {}", t, pos + 1)),
+        };
+        let (op_pos, op) = match self.next()? {
+            (p, Token::Op(op)) => (*p, *op),
+            (p, t) => return Err(format!("Expected a comparison but found {} at column {}", t, p + 1)),
+        };
+        let (value_pos, value) = self.next()?;
+
+        let num_field = match field {
+            "len" => Some(NumField::Len),
+            "gc" => Some(NumField::Gc),
+            "ambig" => Some(NumField::Ambig),
+            "homopolymer" => Some(NumField::Homopolymer),
+            "masked" => Some(NumField::Masked),
+            _ => None,
+        };
+        let text_field = match field {
+            "id" => Some(TextField::Id),
+            "desc" => Some(TextField::Desc),
+            "seq" => Some(TextField::Seq),
+            _ => None,
+        };
+        let bad_op = || Err(format!("Can't compare `{}' with `{}' at column {}", field, op, op_pos + 1));
+        let bad_value = |expected: &str| Err(format!(
+            "Expected {} to compare with `{}' but found {} at column {}", expected, field, value, value_pos + 1));
+
+        match (num_field, text_field, value) {
+            (Some(f), _, Token::Number(v)) => {
+                let op = match op {
+                    "<" => CmpOp::Lt,
+                    "<=" => CmpOp::Le,
+                    ">" => CmpOp::Gt,
+                    ">=" => CmpOp::Ge,
+                    "==" => CmpOp::Eq,
+                    "!=" => CmpOp::Ne,
+                    _ => return bad_op(),
+                };
+                Ok(Expr::Compare(f, op, *v))
+            }
+            (Some(_), _, _) => bad_value("a number"),
+            (_, Some(f), Token::Str(s)) => match op {
+                "==" => Ok(Expr::Equals(f, s.clone())),
+                "!=" => Ok(Expr::Not(Box::new(Expr::Equals(f, s.clone())))),
+                "~" | "!~" => {
+                    let re = Regex::new(s)
+                        .map_err(|e| format!("Bad regular expression at column {}: {}", value_pos + 1, e))?;
+                    let m = Expr::Matches(f, re);
+                    Ok(if op == "~" { m } else { Expr::Not(Box::new(m)) })
+                }
+                _ => bad_op(),
+            },
+            (_, Some(_), _) => bad_value("a quoted string"),
+            _ => Err(format!("Unknown field `{}' at column {}", field, pos + 1)),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bio::seq::fasta::FastaRecord;
+
+    fn rec(descr_line: &str, seq: &str) -> SeqRecord {
+        SeqRecord::Fasta(FastaRecord { descr_line: descr_line.to_string(), seq: seq.to_string() })
+    }
+
+    fn eval(expr: &str, r: &SeqRecord) -> bool {
+        Expr::from_str(expr).unwrap().eval(r)
+    }
+
+    #[test]
+    fn test_eval() {
+        let contig = rec("contig_1 some contig", "ACGTGGCCnnAAAAA");
+        let scaffold = rec("scaffold_2", "ATATATAT");
+
+        let e = "len > 10 && gc < 0.6 && !id ~ \"^scaffold\"";
+        assert!(eval(e, &contig));
+        assert!(!eval(e, &scaffold));
+
+        assert!(eval("homopolymer >= 5 || desc == \"x\"", &contig));
+        assert!(eval("!(ambig > 0.1) && masked == 0", &scaffold));
+        assert!(eval("desc ~ \"some\\\\s+contig\" && seq !~ \"TTT\"", &contig));
+        assert!(eval("id != \"contig_1\" || len < 10", &scaffold));
+        assert!(eval("id ~ \"_\\d+$\"", &contig));
+        assert!(eval("id ~ \"^\\d+$\"", &rec("123", "A")));
+        assert!(!eval("id ~ \"^\\d+$\"", &rec("ddd", "A")));
+        assert!(eval("id == \"a\\\"b\\\\c\"", &rec("a\"b\\c", "A")));
+        assert!(eval("gc > 1e-3 && gc < 1E+1 && masked > -0.5 && len > -1", &contig));
+        assert!(!eval("gc < 2.5e-1", &contig));
+        assert!(!eval("id != \"contig_1\" || len < 10", &contig));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(Expr::from_str("len >").unwrap_err(), "Unexpected end of expression at column 6");
+        assert_eq!(Expr::from_str("size > 3").unwrap_err(), "Unknown field `size' at column 1");
+        assert_eq!(Expr::from_str("id > 3").unwrap_err(),
+            "Expected a quoted string to compare with `id' but found `3' at column 6");
+        assert_eq!(Expr::from_str("len ~ 3").unwrap_err(), "Can't compare `len' with `~' at column 5");
+        assert_eq!(Expr::from_str("(len > 3").unwrap_err(), "Unexpected end of expression at column 9");
+        assert_eq!(Expr::from_str("len > 3 len").unwrap_err(), "Unexpected `len' at column 9");
+        assert!(Expr::from_str("id ~ \"(\"").is_err());
+    }
+}
@@ -0,0 +1,82 @@
+//! Lists of record identifiers to keep or drop.
+
+use std::collections::HashMap;
+use std::io::{
+    self,
+    BufRead,
+};
+
+use bio::seq::header::split_version;
+
+/// A set of identifiers, remembering which have been looked up.
+pub struct IdList {
+    /// Identifiers in the order they were listed.
+    ids: Vec<String>,
+    /// Index into `ids`, and whether it has been seen.
+    lookup: HashMap<String, (usize, bool)>,
+    ignore_version: bool,
+}
+
+impl IdList {
+    /// Read one identifier per line, taking the first word of each. Blank
+    /// lines and lines starting with `#` are skipped.
+    pub fn read<R: BufRead>(reader: R, ignore_version: bool) -> io::Result<IdList> {
+        let mut list = IdList { ids: Vec::new(), lookup: HashMap::new(), ignore_version };
+        for line in reader.lines() {
+            let line = line?;
+            if line.starts_with('#') { continue }
+            if let Some(id) = line.split_whitespace().next() {
+                let key = list.key(id).to_string();
+                let i = list.ids.len();
+                list.lookup.entry(key).or_insert_with(|| (i, false));
+                list.ids.push(id.to_string());
+            }
+        }
+        Ok(list)
+    }
+
+    fn key<'a>(&self, id: &'a str) -> &'a str {
+        if self.ignore_version { split_version(id).0 } else { id }
+    }
+
+    /// True if `id` is in the list, marking it as seen.
+    pub fn check(&mut self, id: &str) -> bool {
+        let key = self.key(id);
+        match self.lookup.get_mut(key) {
+            Some((_, seen)) => { *seen = true; true }
+            None => false,
+        }
+    }
+
+    /// Listed identifiers that were never checked, in the order they were listed.
+    pub fn unseen(&self) -> Vec<&str> {
+        let mut unseen: Vec<usize> = self.lookup.values()
+            .filter(|(_, seen)| !seen)
+            .map(|(i, _)| *i)
+            .collect();
+        unseen.sort_unstable();
+        unseen.iter().map(|i| self.ids[*i].as_str()).collect()
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_id_list() {
+        let input: &[u8] = b"# wanted\nNC_000913.3 ecoli\n\nseq2\nseq3.1\n";
+
+        let mut exact = IdList::read(input, false).unwrap();
+        assert!(exact.check("NC_000913.3"));
+        assert!(!exact.check("NC_000913.2"));
+        assert!(!exact.check("seq3"));
+        assert_eq!(exact.unseen(), vec!["seq2", "seq3.1"]);
+
+        let mut unversioned = IdList::read(input, true).unwrap();
+        assert!(unversioned.check("NC_000913.2"));
+        assert!(unversioned.check("seq3"));
+        assert_eq!(unversioned.unseen(), vec!["seq2"]);
+    }
+}
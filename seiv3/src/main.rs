@@ -2,7 +2,10 @@
 use std::io::{
     self,
     BufRead,
+    BufReader,
 };
+use std::fs::File;
+use std::str::FromStr;
 
 extern crate clap;
 use clap::{
@@ -12,29 +15,35 @@ use clap::{
     crate_authors,
 };
 
-use bio::seq::fasta::*;
+mod expr;
+mod ids;
+mod report;
+use expr::Expr;
+use ids::IdList;
+use report::Report;
 
-use chunks;
+use bio::mask::soft_masked_fraction;
+use bio::seq::record::{SeqReader, SeqRecord};
 
 fn main() -> Result<(), io::Error> {
 
     let matches = App::new("s3iv")
         .version(crate_version!())
         .author(crate_authors!())
-        .about("Seivs (filters) fasta files")
+        .about("Seivs (filters) fasta and fastq files")
 //        .arg(Arg::with_name(""))
         .arg(Arg::with_name("seqIn")
             .short("i")
             .long("seqIn")
             .multiple(true)
             .takes_value(true)
-            .help("Sequence input file. If not provided, defaults to STDIN."))
+            .help("FASTA or FASTQ sequence input file. If not provided, defaults to STDIN."))
         .arg(Arg::with_name("seqOut")
             .short("o")
             .long("seqOut")
             .multiple(false)
             .takes_value(true)
-            .help("Sequence output file. If not provided, defaults to STDOUT."))
+            .help("Sequence output file, in the same format as the input. If not provided, defaults to STDOUT."))
         .arg(Arg::with_name("minLength")
             .short("m")
             .long("minLength")
@@ -42,13 +51,20 @@ fn main() -> Result<(), io::Error> {
             .takes_value(true)
             .required(false)
             .help("Minimum sequence length. By default, no sequences are rejected for being too short."))
-        .arg(Arg::with_name("minLength")
+        .arg(Arg::with_name("maxLength")
             .short("x")
             .long("maxLength")
             .multiple(false)
             .takes_value(true)
             .required(false)
             .help("Maximum sequence length. By default, no sequences are rejected for being too long."))
+        .arg(Arg::with_name("maxMaskedFraction")
+            .long("maxMaskedFraction")
+            .multiple(false)
+            .takes_value(true)
+            .required(false)
+            .validator(cli::validate_fraction)
+            .help("Maximum fraction of soft-masked (lower case) bases, between 0 and 1. By default, no sequences are rejected for being masked."))
         .arg(Arg::with_name("polyN")
             .short("n")
             .long("polyN")
@@ -56,6 +72,61 @@ fn main() -> Result<(), io::Error> {
             .takes_value(false)
             .required(false)
             .help("Enable filtering out of poly-n sequences."))
+        .arg(Arg::with_name("filter")
+            .short("f")
+            .long("filter")
+            .multiple(false)
+            .takes_value(true)
+            .required(false)
+            .validator(|e| Expr::from_str(&e).map(|_| ()))
+            .help("Only keep records matching an expression, such as: len > 500 && gc < 0.6 && !id ~ \"^scaffold\". Numeric fields len, gc, ambig, homopolymer and masked compare with < <= > >= == !=. Text fields id, desc and seq compare with == != or match a regular expression with ~ !~. Combine with ! && || and parentheses."))
+        .arg(Arg::with_name("includeIds")
+            .long("includeIds")
+            .multiple(false)
+            .takes_value(true)
+            .required(false)
+            .help("File listing the identifiers of records to keep, one per line. Ids that are never seen are reported on STDERR."))
+        .arg(Arg::with_name("excludeIds")
+            .long("excludeIds")
+            .multiple(false)
+            .takes_value(true)
+            .required(false)
+            .help("File listing the identifiers of records to drop, one per line. Ids that are never seen are reported on STDERR."))
+        .arg(Arg::with_name("ignoreVersion")
+            .long("ignoreVersion")
+            .multiple(false)
+            .takes_value(false)
+            .required(false)
+            .help("Ignore accession version suffixes, such as the .3 of NC_000913.3, when matching --includeIds and --excludeIds."))
+        .arg(Arg::with_name("rejectedOut")
+            .long("rejectedOut")
+            .multiple(false)
+            .takes_value(true)
+            .required(false)
+            .help("Sequence output file for rejected records, with the rules that rejected them added to their description as rejected=rule,rule."))
+        .arg(Arg::with_name("report")
+            .long("report")
+            .multiple(false)
+            .takes_value(false)
+            .required(false)
+            .help("Write a summary of records and bases kept, and rejected by each rule, to STDERR."))
+        .arg(Arg::with_name("reportJson")
+            .long("reportJson")
+            .multiple(false)
+            .takes_value(true)
+            .required(false)
+            .help("Write the summary of records and bases kept and rejected to this file as JSON."))
+        .arg(Arg::with_name("maxLowComplexity")
+            .long("maxLowComplexity")
+            .multiple(false)
+            .takes_value(true)
+            .required(false)
+            .validator(cli::validate_fraction)
+            .help("Maximum fraction of bases in low-complexity regions, between 0 and 1. By default, no sequences are rejected for low complexity."))
+        .args(&cli::complexity_args())
+        .args(&cli::fasta_format_args())
+        .args(&cli::validation_args())
+        .arg(cli::error_policy_arg())
         .get_matches();
 
 
@@ -66,19 +137,19 @@ fn main() -> Result<(), io::Error> {
         chunks::read_from_files_or_stdin(matches.values_of("seqIn"))?;
 
     // true if the sequence is all N, false otherwise
-    fn seiv_n(fasta: &FastaRecord) -> bool {
-        fasta.seq.chars().all(|c| c == 'n' || c == 'N')
+    fn seiv_n(fasta: &SeqRecord) -> bool {
+        fasta.seq().chars().all(|c| c == 'n' || c == 'N')
     }
-    let filter_n = |fr: &FastaRecord| (matches.is_present("polyN") &&
-        seiv_n(fr));
+    let filter_n = |fr: &SeqRecord| matches.is_present("polyN") &&
+        seiv_n(fr);
 
     // true if the sequence is too short
     let short = matches.value_of("minLength")
         .iter()
         .flat_map(|m| m.parse::<usize>().ok())
         .next();
-    let filter_short = |fr : &FastaRecord| match short {
-        Some(m) => fr.seq.len() < m,
+    let filter_short = |fr : &SeqRecord| match short {
+        Some(m) => fr.seq().len() < m,
         None    => false
     };
 
@@ -87,34 +158,123 @@ fn main() -> Result<(), io::Error> {
         .iter()
         .flat_map(|m| m.parse::<usize>().ok())
         .next();
-    let filter_long  = |fr : &FastaRecord| match long {
-        Some(x) => fr.seq.len() > x,
+    let filter_long  = |fr : &SeqRecord| match long {
+        Some(x) => fr.seq().len() > x,
         None    => false
     };
 
-    let reject_fasta = |fr: &FastaRecord| {
-        let is_n = filter_n(&fr);
-        let is_short = filter_short(&fr);
-        let is_long = filter_long(&fr);
-        is_n || is_short || is_long
+    // true if too much of the sequence is soft-masked
+    let max_masked = matches.value_of("maxMaskedFraction")
+        .map(|m| m.parse::<f64>().unwrap());
+    let filter_masked = |fr : &SeqRecord| match max_masked {
+        Some(x) => soft_masked_fraction(fr.seq()) > x,
+        None    => false
+    };
+
+    // true if the sequence isn't matched by the filter expression
+    let expr = matches.value_of("filter").map(|e| Expr::from_str(e).unwrap());
+    let filter_expr = |fr : &SeqRecord| match &expr {
+        Some(e) => !e.eval(fr),
+        None    => false
     };
 
-    let fasta = FastaFormat::new();
-    for mut in_reader in ins {
-        let mut seq_txt = String::new();
-        in_reader.read_to_string(&mut seq_txt)?;
-        match parse_fastas(&seq_txt) {
-            Ok((_, in_seqs)) => {
-                 /* without explicit lambda, fr became &&fr */
-                let filtered = in_seqs.iter().filter(|fr| !reject_fasta(*fr));
-                for in_seq in filtered {
-                    in_seq.write(&fasta, &mut out)?;
+    // true if too much of the sequence is low-complexity
+    let low_complexity = cli::low_complexity(&matches);
+    let max_low_complexity = matches.value_of("maxLowComplexity")
+        .map(|m| m.parse::<f64>().unwrap());
+    let filter_low_complexity = |fr : &SeqRecord| match max_low_complexity {
+        Some(x) => low_complexity.fraction(fr.seq()) > x,
+        None    => false
+    };
+
+    // the rules that reject the sequence, named after their arguments
+    let reject_fasta = |fr: &SeqRecord| {
+        let mut reasons = Vec::new();
+        if filter_n(fr) { reasons.push("polyN") }
+        if filter_short(fr) { reasons.push("minLength") }
+        if filter_long(fr) { reasons.push("maxLength") }
+        if filter_masked(fr) { reasons.push("maxMaskedFraction") }
+        if filter_low_complexity(fr) { reasons.push("maxLowComplexity") }
+        if filter_expr(fr) { reasons.push("filter") }
+        reasons
+    };
+
+    let ignore_version = matches.is_present("ignoreVersion");
+    let read_ids = |arg: &str| -> io::Result<Option<IdList>> {
+        match matches.value_of(arg) {
+            Some(f) => Ok(Some(IdList::read(BufReader::new(File::open(f)?), ignore_version)?)),
+            None => Ok(None),
+        }
+    };
+    let mut include = read_ids("includeIds")?;
+    let mut exclude = read_ids("excludeIds")?;
+
+    let mut rejected_out = match matches.value_of("rejectedOut") {
+        Some(r) => Some(chunks::write_to_file_or_stdout(Some(r))?),
+        None => None,
+    };
+    let mut report = Report::default();
+    let lc_mask = cli::low_complexity_mask(&matches);
+
+    let fasta = cli::fasta_format(&matches);
+    let validator = cli::validator(&matches);
+    let names = cli::input_names(matches.values_of("seqIn"));
+    let mut errors = cli::ErrorHandler::new(cli::ErrorPolicy::from_matches(&matches));
+    for (name, in_reader) in names.iter().zip(ins) {
+        let mut reader = SeqReader::new(in_reader)?.with_validator(validator);
+        while let Some(in_seq) = reader.next() {
+            errors.warn(name, reader.take_warnings());
+            let mut in_seq = match errors.record(name, in_seq)? {
+                Some(s) => s,
+                None => continue,
+            };
+            if let Some(m) = lc_mask {
+                in_seq.set_seq(low_complexity.mask(in_seq.seq(), m));
+            }
+            // check both lists, so that all seen ids are recorded
+            let id = in_seq.description().identifier.unwrap_or_default();
+            let is_included = include.as_mut().is_none_or(|l| l.check(&id));
+            let is_excluded = exclude.as_mut().is_some_and(|l| l.check(&id));
+
+            let mut reasons = reject_fasta(&in_seq);
+            if !is_included { reasons.push("includeIds") }
+            if is_excluded { reasons.push("excludeIds") }
+
+            let bases = in_seq.seq().len();
+            if reasons.is_empty() {
+                report.keep(bases);
+                in_seq.write(&fasta, &mut out)?;
+            } else {
+                report.reject(&reasons, bases);
+                if let Some(r) = rejected_out.as_mut() {
+                    let mut in_seq = in_seq;
+                    let descr = in_seq.description().description;
+                    let rejected = format!("rejected={}", reasons.join(","));
+                    in_seq.set_description(Some(&match descr {
+                        Some(d) => format!("{} {}", d, rejected),
+                        None => rejected,
+                    }));
+                    in_seq.write(&fasta, r)?;
                 }
-            },
-            Err(e) => println!("Error parsing fasta input:\n{:#?}\n", e)
+            }
+        }
+    }
+
+    if matches.is_present("report") {
+        report.write_text(&mut io::stderr())?;
+    }
+    if let Some(f) = matches.value_of("reportJson") {
+        report.write_json(&mut File::create(f)?)?;
+    }
+
+    for (arg, list) in &[("includeIds", &include), ("excludeIds", &exclude)] {
+        if let Some(list) = list {
+            for id in list.unseen() {
+                eprintln!("Warning: id `{}' from --{} was never seen", id, arg);
+            }
         }
     }
 
-    Ok(())
+    errors.finish()
 }
 
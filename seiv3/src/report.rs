@@ -0,0 +1,86 @@
+//! Counts of the records and bases kept and rejected, overall and per rule.
+
+use std::io::{
+    self,
+    Write,
+};
+
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub struct Tally {
+    pub records: usize,
+    pub bases: usize,
+}
+
+impl Tally {
+    fn add(&mut self, bases: usize) {
+        self.records += 1;
+        self.bases += bases;
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Report {
+    pub kept: Tally,
+    pub rejected: Tally,
+    /// Records rejected by each rule, in the order the rules were first hit.
+    /// A record rejected by several rules is counted against each of them.
+    pub rules: Vec<(&'static str, Tally)>,
+}
+
+impl Report {
+    pub fn keep(&mut self, bases: usize) {
+        self.kept.add(bases);
+    }
+
+    pub fn reject(&mut self, reasons: &[&'static str], bases: usize) {
+        self.rejected.add(bases);
+        for reason in reasons {
+            match self.rules.iter_mut().find(|(r, _)| r == reason) {
+                Some((_, t)) => t.add(bases),
+                None => {
+                    let mut t = Tally::default();
+                    t.add(bases);
+                    self.rules.push((reason, t));
+                }
+            }
+        }
+    }
+
+    pub fn write_text<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        writeln!(out, "kept\t{} records\t{} bases", self.kept.records, self.kept.bases)?;
+        writeln!(out, "rejected\t{} records\t{} bases", self.rejected.records, self.rejected.bases)?;
+        for (rule, t) in &self.rules {
+            writeln!(out, "  {}\t{} records\t{} bases", rule, t.records, t.bases)?;
+        }
+        Ok(())
+    }
+
+    pub fn write_json<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let tally = |t: &Tally| format!("{{\"records\": {}, \"bases\": {}}}", t.records, t.bases);
+        let rules: Vec<String> = self.rules.iter()
+            .map(|(rule, t)| format!("\"{}\": {}", rule, tally(t)))
+            .collect();
+        writeln!(out, "{{\"kept\": {}, \"rejected\": {}, \"rules\": {{{}}}}}",
+            tally(&self.kept), tally(&self.rejected), rules.join(", "))
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_report() {
+        let mut report = Report::default();
+        report.keep(10);
+        report.reject(&["minLength", "polyN"], 3);
+        report.reject(&["polyN"], 20);
+
+        let mut json = Vec::new();
+        report.write_json(&mut json).unwrap();
+        assert_eq!(String::from_utf8(json).unwrap(),
+            "{\"kept\": {\"records\": 1, \"bases\": 10}, \"rejected\": {\"records\": 2, \"bases\": 23}, \
+            \"rules\": {\"minLength\": {\"records\": 1, \"bases\": 3}, \"polyN\": {\"records\": 2, \"bases\": 23}}}\n");
+    }
+}
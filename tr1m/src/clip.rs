@@ -0,0 +1,97 @@
+//! Working out which parts of a sequence survive clipping.
+
+use std::ops::Range;
+use std::str::FromStr;
+
+/// What to do with a sequence when a clipped region leaves bases either side
+/// of it.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Internal {
+    /// Write each remaining part as its own record.
+    Split,
+    /// Join the remaining parts back together as one record.
+    Excise,
+}
+
+impl FromStr for Internal {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "split" => Ok(Internal::Split),
+            "excise" => Ok(Internal::Excise),
+            e => Err(format!("Unknown internal clip handling `{}'. Expected split or excise", e)),
+        }
+    }
+}
+
+/// Clamp `regions` to a sequence of length `len`, and merge any that overlap
+/// or touch. The result is in order and has no empty regions.
+pub fn merge(mut regions: Vec<Range<usize>>, len: usize) -> Vec<Range<usize>> {
+    regions.sort_unstable_by_key(|r| r.start);
+    let mut merged: Vec<Range<usize>> = Vec::new();
+    for r in regions {
+        let r = r.start.min(len)..r.end.min(len);
+        if r.is_empty() { continue }
+        match merged.last_mut() {
+            Some(m) if m.end >= r.start => m.end = m.end.max(r.end),
+            _ => merged.push(r),
+        }
+    }
+    merged
+}
+
+/// The parts of a sequence of length `len` outside of the merged `clipped`
+/// regions.
+pub fn kept(clipped: &[Range<usize>], len: usize) -> Vec<Range<usize>> {
+    let mut kept = Vec::new();
+    let mut from = 0;
+    for r in clipped {
+        if r.start > from {
+            kept.push(from..r.start);
+        }
+        from = r.end;
+    }
+    if from < len {
+        kept.push(from..len);
+    }
+    kept
+}
+
+/// Regions as comma-separated `start-end` pairs, counting from 1 and
+/// inclusive, or `.` if there are none.
+pub fn format_regions(regions: &[Range<usize>]) -> String {
+    if regions.is_empty() {
+        return ".".to_string()
+    }
+    regions.iter()
+        .map(|r| format!("{}-{}", r.start + 1, r.end))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_merge() {
+        assert_eq!(merge(vec![20..30, 0..5, 3..8, 8..10, 50..60, 95..200], 100), vec![0..10, 20..30, 50..60, 95..100]);
+        assert_eq!(merge(vec![120..130, 4..4], 100), Vec::<Range<usize>>::new());
+    }
+
+    #[test]
+    fn test_kept() {
+        assert_eq!(kept(&[0..10, 20..30], 40), vec![10..20, 30..40]);
+        assert_eq!(kept(&[Range { start: 5, end: 40 }], 40), vec![Range { start: 0, end: 5 }]);
+        assert_eq!(kept(&[Range { start: 0, end: 40 }], 40), vec![]);
+        assert_eq!(kept(&[], 40), vec![Range { start: 0, end: 40 }]);
+    }
+
+    #[test]
+    fn test_format_regions() {
+        assert_eq!(format_regions(&[0..10, 20..30]), "1-10,21-30");
+        assert_eq!(format_regions(&[]), ".");
+    }
+}
@@ -0,0 +1,104 @@
+//! Moving annotations from raw sequences onto their clipped records.
+
+use std::ops::Range;
+
+use bio::seq::gff3::{GffRecord, OneBased, Phase, Strand};
+
+/// A record written from a raw sequence, made of the raw bases in `kept`
+/// joined in order, counting from 0.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Placement {
+    pub id: String,
+    pub kept: Vec<Range<usize>>,
+}
+
+/// Copies of `feature` on each placement that it overlaps, with shifted
+/// coordinates. Copies that lose any bases are truncated and given a
+/// `partial=true` attribute, and the phase of a CDS is updated for any bases
+/// lost from its 5' end. Returns nothing if the feature is clipped away.
+pub fn lift(feature: &GffRecord, placements: &[Placement]) -> Vec<GffRecord> {
+    let start = feature.start.at().saturating_sub(1) as usize;
+    let end = feature.end.at() as usize;
+    placements.iter()
+        .filter_map(|p| lift_onto(feature, start..end, p))
+        .collect()
+}
+
+fn lift_onto(feature: &GffRecord, raw: Range<usize>, placement: &Placement) -> Option<GffRecord> {
+    // the raw and lifted extents of the overlapped bases
+    let mut raw_extent: Option<Range<usize>> = None;
+    let mut lifted: Option<Range<usize>> = None;
+    let mut overlapped = 0;
+    let mut offset = 0;
+    for k in &placement.kept {
+        let ov = raw.start.max(k.start)..raw.end.min(k.end);
+        if !ov.is_empty() {
+            let at = offset + ov.start - k.start..offset + ov.end - k.start;
+            overlapped += ov.len();
+            raw_extent = Some(raw_extent.map_or(ov.start, |r| r.start)..ov.end);
+            lifted = Some(lifted.map_or(at.start, |l| l.start)..at.end);
+        }
+        offset += k.len();
+    }
+    let (raw_extent, lifted) = (raw_extent?, lifted?);
+
+    let mut rec = feature.clone();
+    rec.seq_id = placement.id.clone();
+    rec.start = OneBased::new(lifted.start as u64 + 1);
+    rec.end = OneBased::new(lifted.end as u64);
+    if overlapped < raw.len() {
+        rec.attributes.set("partial", "true");
+        let lost_5p = match feature.strand {
+            Strand::Negative => raw.end - raw_extent.end,
+            _ => raw_extent.start - raw.start,
+        };
+        if let Some(p) = feature.phase.phase() {
+            rec.phase = Phase::new(Some(((p as usize + 3 - lost_5p % 3) % 3) as u8));
+        }
+    }
+    Some(rec)
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::str::FromStr;
+
+    fn placement(id: &str, kept: &[Range<usize>]) -> Placement {
+        Placement { id: id.to_string(), kept: kept.to_vec() }
+    }
+
+    #[test]
+    fn test_lift_shifts_and_truncates() {
+        let gene = GffRecord::from_str("s1\t.\tgene\t21\t40\t.\t+\t.\tID=g1").unwrap();
+        let clipped = vec![placement("s1_clipped_10", &[Range { start: 10, end: 100 }])];
+        let lifted = lift(&gene, &clipped);
+        assert_eq!(lifted.len(), 1);
+        assert_eq!(lifted[0].to_string(), "s1_clipped_10\t.\tgene\t11\t30\t.\t+\t.\tID=g1");
+
+        let cds = GffRecord::from_str("s1\t.\tCDS\t4\t40\t.\t+\t0\tID=c1").unwrap();
+        assert_eq!(lift(&cds, &clipped)[0].to_string(), "s1_clipped_10\t.\tCDS\t1\t30\t.\t+\t2\tID=c1;partial=true");
+
+        let rev = GffRecord::from_str("s1\t.\tCDS\t50\t110\t.\t-\t1\tID=c2").unwrap();
+        assert_eq!(lift(&rev, &clipped)[0].to_string(), "s1_clipped_10\t.\tCDS\t40\t90\t.\t-\t0\tID=c2;partial=true");
+
+        let gone = GffRecord::from_str("s1\t.\tgene\t1\t10\t.\t+\t.\tID=g2").unwrap();
+        assert!(lift(&gone, &clipped).is_empty());
+    }
+
+    #[test]
+    fn test_lift_across_internal_clips() {
+        let gene = GffRecord::from_str("s1\t.\tgene\t6\t35\t.\t+\t.\tID=g1").unwrap();
+
+        let split = vec![placement("s1_part1", &[Range { start: 0, end: 10 }]), placement("s1_part2", &[Range { start: 20, end: 40 }])];
+        let parts: Vec<String> = lift(&gene, &split).iter().map(ToString::to_string).collect();
+        assert_eq!(parts, vec![
+            "s1_part1\t.\tgene\t6\t10\t.\t+\t.\tID=g1;partial=true",
+            "s1_part2\t.\tgene\t1\t15\t.\t+\t.\tID=g1;partial=true",
+        ]);
+
+        let excised = vec![placement("s1_clipped_10", &[0..10, 20..40])];
+        assert_eq!(lift(&gene, &excised)[0].to_string(), "s1_clipped_10\t.\tgene\t6\t25\t.\t+\t.\tID=g1;partial=true");
+    }
+}
@@ -1,122 +1,524 @@
 
-use std::io::Write;
+use std::collections::HashSet;
+use std::io::{
+    BufRead,
+    BufReader,
+    Write,
+};
 use std::io;
+use std::ops::Range;
 use std::str::FromStr;
 use std::fs;
 
 use clap::{
     Arg,
     App,
+    ArgMatches,
     crate_name,
     crate_version,
     crate_authors,
 };
 
-use bio::seq::fasta::*;
-use bio::seq::gff3::{GffRecord, OneBased};
+use bio::mask::MaskMode;
+use bio::mask::mask_intervals;
+use bio::seq::record::SeqReader;
+use bio::seq::bed::BedReader;
+use bio::seq::error::ReadError;
+use bio::seq::gff3::{GffIndex, GffReader, GffRecord};
 
-use chunks;
+mod clip;
+use clip::Internal;
+mod lift;
+use lift::Placement;
+mod trim;
+use trim::{Adapter, QualityTrim, ReadTrimmer};
 
 fn main() -> Result<(), io::Error> {
-    let matches = App::new(crate_name!())
+    let matches = app().get_matches();
+
+    let out =
+        chunks::write_to_file_or_stdout(matches.value_of("seqOut"))
+        .expect("Failed to open output file for writing");
+
+    let ins = chunks::read_from_files_or_stdin(matches.values_of("seqIn"))
+        .expect("Failed to open input file for reading");
+
+    let errors = cli::ErrorHandler::new(cli::ErrorPolicy::from_matches(&matches));
+
+    if !matches.is_present("gff") && !matches.is_present("bed") {
+        return trim_reads(&matches, out, ins, errors)
+    }
+    clip_regions(&matches, out, ins, errors)
+}
+
+/// Options trimming reads, which can't be used with --gff or --bed, nor with
+/// the options for clipping regions.
+const TRIM_ARGS: [&str; 4] = ["qualityTrim", "adapter", "minLength", "mateIn"];
+
+fn app<'a, 'b>() -> App<'a, 'b> {
+    App::new(crate_name!())
         .version(crate_version!())
         .author(crate_authors!())
-        .about("Converts embl files to fasta files")
+        .about("Clips regions listed in a GFF3 or BED file out of sequences, or quality and adapter trims reads")
         .arg(Arg::with_name("seqIn")
             .short("i")
             .long("seqIn")
             .multiple(false)
             .takes_value(true)
-            .help("EMBL-formatted sequence input file. If not provided, defaults to STDIN.")
+            .help("FASTA or FASTQ sequence input file. If not provided, defaults to STDIN.")
         )
         .arg(Arg::with_name("seqOut")
             .short("o")
             .long("seqOut")
             .multiple(false)
             .takes_value(true)
-            .help("Sequence output file. If not provided, defaults to STDOUT."))
+            .help("Sequence output file, in the same format as the input. If not provided, defaults to STDOUT."))
         .arg(Arg::with_name("gff")
             .short("g")
             .long("gff")
             .multiple(false)
             .takes_value(true)
-            .help("GFF3 file containing regions to clip"))
+            .required_unless_one(&["bed", "qualityTrim", "adapter", "minLength"])
+            .help("GFF3 file containing regions to clip. Regions may be anywhere in a sequence, and overlapping regions are merged."))
+        .arg(Arg::with_name("bed")
+            .short("b")
+            .long("bed")
+            .multiple(false)
+            .takes_value(true)
+            .conflicts_with("gff")
+            .help("BED file containing regions to clip, as an alternative to --gff. The name column is used as the feature type for --maskTypes."))
+        .arg(Arg::with_name("internal")
+            .long("internal")
+            .multiple(false)
+            .takes_value(true)
+            .possible_values(&["split", "excise"])
+            .conflicts_with_all(&TRIM_ARGS)
+            .help("How to handle a clipped region with bases left either side of it. Split writes each remaining part as a record with a _part<n> suffix. Excise joins the remaining parts into one record. Defaults to split."))
+        .arg(Arg::with_name("mask")
+            .long("mask")
+            .multiple(false)
+            .takes_value(true)
+            .possible_values(&["soft", "hard"])
+            .conflicts_with_all(&TRIM_ARGS)
+            .help("Mask regions rather than clipping them, either in lower case (soft) or with N (hard), leaving sequence lengths and identifiers unchanged."))
+        .arg(Arg::with_name("maskTypes")
+            .long("maskTypes")
+            .multiple(true)
+            .takes_value(true)
+            .use_delimiter(true)
+            .requires("mask")
+            .conflicts_with_all(&TRIM_ARGS)
+            .help("Only mask regions with these GFF feature types, and clip the rest. May be given more than once, or as a comma-separated list."))
+        .arg(Arg::with_name("annotation")
+            .short("a")
+            .long("annotation")
+            .multiple(false)
+            .takes_value(true)
+            .requires("annotationOut")
+            .conflicts_with_all(&TRIM_ARGS)
+            .help("GFF3 file of annotations on the raw sequences, to be moved onto the clipped ones."))
+        .arg(Arg::with_name("annotationOut")
+            .long("annotationOut")
+            .multiple(false)
+            .takes_value(true)
+            .requires("annotation")
+            .conflicts_with_all(&TRIM_ARGS)
+            .help("GFF3 file to write the moved annotations to. Features on clipped ids have shifted coordinates. Features that lose bases are truncated and marked partial=true, and features clipped away entirely are reported on STDERR."))
         .arg(Arg::with_name("mapping")
             .short("m")
             .long("mapping")
             .multiple(false)
             .takes_value(true)
-            .help("Name of mapping file documenting the raw and clipped identifiers. Only generates mapping file if supplied."))
-        .get_matches();
+            .help("Name of mapping file documenting the raw and clipped identifiers. Each line holds the raw and clipped identifiers, the raw coordinates kept in the clipped record, those clipped from the whole sequence and those masked, as comma-separated 1-based inclusive start-end pairs. Records clipped entirely have a clipped identifier of `.'. Only generates mapping file if supplied."))
+        .arg(Arg::with_name("qualityTrim")
+            .long("qualityTrim")
+            .multiple(false)
+            .takes_value(true)
+            .possible_values(&["window", "mott"])
+            .conflicts_with_all(&["gff", "bed"])
+            .help("Quality trim FASTQ reads. Window cuts each read at the start of the first window with a mean quality below --minQuality. Mott keeps the stretch of the read with the largest sum of quality less --minQuality, trimming both ends."))
+        .arg(Arg::with_name("minQuality")
+            .long("minQuality")
+            .multiple(false)
+            .takes_value(true)
+            .validator(cli::validate::<u8>)
+            .help("Phred quality threshold for --qualityTrim. Defaults to 20."))
+        .arg(Arg::with_name("qualityWindow")
+            .long("qualityWindow")
+            .multiple(false)
+            .takes_value(true)
+            .validator(cli::validate::<usize>)
+            .help("Window length for --qualityTrim window. Defaults to 4."))
+        .arg(Arg::with_name("phredOffset")
+            .long("phredOffset")
+            .multiple(false)
+            .takes_value(true)
+            .validator(cli::validate::<u8>)
+            .help("Offset of the encoded FASTQ qualities. Defaults to 33."))
+        .arg(Arg::with_name("adapter")
+            .long("adapter")
+            .multiple(false)
+            .takes_value(true)
+            .conflicts_with_all(&["gff", "bed"])
+            .help("Adapter to trim from the 3' end of reads, along with everything after it. Adapters running off the end of a read are trimmed if at least --adapterMinOverlap bases match."))
+        .arg(Arg::with_name("mateAdapter")
+            .long("mateAdapter")
+            .multiple(false)
+            .takes_value(true)
+            .requires("mateIn")
+            .help("Adapter to trim from the mate reads. Defaults to --adapter."))
+        .arg(Arg::with_name("adapterMismatches")
+            .long("adapterMismatches")
+            .multiple(false)
+            .takes_value(true)
+            .validator(cli::validate::<usize>)
+            .help("Mismatches allowed against the whole adapter. Partial adapters at the end of a read allow proportionally fewer. Defaults to 1."))
+        .arg(Arg::with_name("adapterMinOverlap")
+            .long("adapterMinOverlap")
+            .multiple(false)
+            .takes_value(true)
+            .validator(cli::validate::<usize>)
+            .help("Fewest adapter bases that must match at the end of a read for it to be trimmed. Defaults to 3."))
+        .arg(Arg::with_name("minLength")
+            .long("minLength")
+            .multiple(false)
+            .takes_value(true)
+            .conflicts_with_all(&["gff", "bed"])
+            .validator(cli::validate::<usize>)
+            .help("Drop reads shorter than this after trimming. With --mateIn, the pair is dropped if either read is too short."))
+        .arg(Arg::with_name("mateIn")
+            .long("mateIn")
+            .multiple(false)
+            .takes_value(true)
+            .requires("mateOut")
+            .conflicts_with_all(&["gff", "bed"])
+            .help("Mate reads of paired-end --seqIn reads, in the same order. Pairs are trimmed and dropped together."))
+        .arg(Arg::with_name("mateOut")
+            .long("mateOut")
+            .multiple(false)
+            .takes_value(true)
+            .requires("mateIn")
+            .help("Output file for the trimmed mate reads."))
+        .arg(cli::accession_arg())
+        .args(&cli::fasta_format_args())
+        .args(&cli::validation_args())
+        .arg(cli::error_policy_arg())
+}
 
-    let mut out =
-        chunks::write_to_file_or_stdout(matches.value_of("seqOut"))
-        .expect("Failed to open output file for writing");
+/// Clip or mask the regions listed in the --gff or --bed file, moving any
+/// annotations onto the clipped sequences.
+fn clip_regions(matches: &ArgMatches, mut out: Box<dyn Write>, ins: Vec<Box<dyn BufRead>>, mut errors: cli::ErrorHandler) -> io::Result<()> {
+    let mask = matches.value_of("mask").map(|m| MaskMode::from_str(m).unwrap());
+    let mask_types: Option<Vec<&str>> = matches.values_of("maskTypes").map(|ts| ts.collect());
+    let masks_type = |t: &str| mask.is_some() && mask_types.as_ref().is_none_or(|ts| ts.contains(&t));
 
-    let ins = chunks::read_from_files_or_stdin(matches.values_of("seqIn"))
-        .expect("Failed to open input file for reading");
+    let regions = match (matches.value_of("gff"), matches.value_of("bed")) {
+        (Some(gff_file_name), _) => read_gff(gff_file_name, &mut errors)?,
+        (None, Some(bed_file_name)) => read_bed(bed_file_name, &mut errors)?,
+        (None, None) => unreachable!("--gff or --bed is required"),
+    };
+    // the regions on a sequence to mask, or else to clip
+    let regions = |seq_id: &str, masked: bool| regions.records(seq_id)
+        .filter(|g| masks_type(&g.feature_type) == masked)
+        .map(|g| { let r = g.range(); r.start as usize..r.end as usize })
+        .collect::<Vec<_>>();
 
-    let gff: Vec<GffRecord> = {
-        let gff_file_name = matches.value_of("gff")
-            .expect("Must provide a gff file");
-        let txt = fs::read_to_string(gff_file_name).expect("Could not read the gff file");
-        txt.lines().filter(|l| !l.starts_with('#'))
-            .map(|l| FromStr::from_str(l)
-            .expect("Failed to parse gff line"))
-            .collect()
+    let annotations = match matches.value_of("annotation") {
+        Some(annotation_file_name) => read_gff(annotation_file_name, &mut errors)?,
+        None => GffIndex::default(),
+    };
+    let mut lifted_ids = HashSet::new();
+    let mut annotation_out = match matches.value_of("annotationOut") {
+        Some(f) => {
+            let mut f = fs::File::create(f)?;
+            writeln!(f, "##gff-version 3")?;
+            Some(f)
+        }
+        None => None,
     };
 
     let mut mapping = matches.value_of("mapping").map(|m| fs::File::create(m)
         .expect("Problem opening mapping file for writing"));
 
-    let mut write_mapping = move |from: &str, to: &str| mapping.iter_mut().for_each(
-        |f| writeln!(f, "{}\t{}", from, to).expect("Unable to write mapping pair to file"));
-
-
-    let delim = chunks::Delim::new(b">", false);
-    let fasta = FastaFormat::new();
-    for in_reader in ins {
-        for chunk in chunks::chunks(in_reader, &delim) {
-            let chunk = chunk.expect("Failed to read chunk");
-            let chunk_text = std::str::from_utf8(&chunk).unwrap();
-            match parse_fastas(&chunk_text) {
-                Ok((_, in_seqs)) => for in_seq in in_seqs {
-                    let fd = FastaDescription::read(&in_seq.descr_line);
-                    match fd.identifier {
-                        Some(id) => {
-                            // println!("Got fasta with id {:?}", id);
-                            let clps = gff.iter()
-                                .filter(|g| g.seq_id == id && g.start == OneBased::new(1))
-                                .map(|g| g.end.at())
-                                .max();
-                            match clps {
-                                Some(clip) => {
-                                    // println!("Got fasta entry with id {} and clip {}. Writing unchanged.", id, clip);
-                                    let clipped_id = format!("{}_clipped_{}", id, clip);
-                                    let descr_line = FastaRecord::descr_line(Some(&clipped_id), fd.description.as_ref().map(String::as_ref));
-                                    let clipped_seq = in_seq.seq[(clip as usize)..].to_string();
-                                    let clipped_rec = FastaRecord { descr_line, seq: clipped_seq };
-                                    // in_seq.write(&fasta, &mut out)?;
-                                    write_mapping(&id, &clipped_id);
-                                    clipped_rec.write(&fasta, &mut out)?;
-                                }
-                                None => {
-                                    // println!("Got fasta entry with id {} but no clip. Writing unchanged.", id);
-                                    write_mapping(&id, &id);
-                                    in_seq.write(&fasta, &mut out)?;
-                                }
+    let mut write_mapping = move |from: &str, to: &str, kept: &[Range<usize>], clipped: &[Range<usize>], masked: &[Range<usize>]| mapping.iter_mut().for_each(
+        |f| writeln!(f, "{}\t{}\t{}\t{}\t{}", from, to,
+                clip::format_regions(kept), clip::format_regions(clipped), clip::format_regions(masked))
+            .expect("Unable to write mapping pair to file"));
+
+
+    let fasta = cli::fasta_format(matches);
+    let id_key = cli::IdKey::from_matches(matches);
+    let internal = Internal::from_str(matches.value_of("internal").unwrap_or("split")).unwrap();
+    let validator = cli::validator(matches);
+    let names = cli::input_names(matches.values_of("seqIn"));
+    for (name, in_reader) in names.iter().zip(ins) {
+        let mut reader = SeqReader::new(in_reader)?.with_validator(validator);
+        while let Some(in_seq) = reader.next() {
+            errors.warn(name, reader.take_warnings());
+            let mut in_seq = match errors.record(name, in_seq)? {
+                Some(s) => s,
+                None => continue,
+            };
+            let fd = in_seq.description();
+            let len = in_seq.seq().len();
+            match (fd.identifier.clone(), id_key.key(&fd)) {
+                (Some(id), Some(key)) => {
+                    let masked = clip::merge(regions(&key, true), len);
+                    if let (Some(m), false) = (mask, masked.is_empty()) {
+                        in_seq.set_seq(mask_intervals(in_seq.seq(), &masked, m));
+                    }
+                    let clipped = clip::merge(regions(&key, false), len);
+                    let kept = clip::kept(&clipped, len);
+                    let placements = if clipped.is_empty() {
+                        vec![Placement { id: id.clone(), kept: kept.clone() }]
+                    } else if kept.is_empty() {
+                        write_mapping(&id, ".", &kept, &clipped, &masked);
+                        vec![]
+                    } else if kept.len() == 1 || internal == Internal::Excise {
+                        let clipped_len: usize = clipped.iter().map(|r| r.len()).sum();
+                        vec![Placement { id: format!("{}_clipped_{}", id, clipped_len), kept: kept.clone() }]
+                    } else {
+                        kept.iter().enumerate()
+                            .map(|(p, part)| Placement { id: format!("{}_part{}", id, p + 1), kept: vec![part.clone()] })
+                            .collect()
+                    };
+
+                    for p in &placements {
+                        let mut rec = in_seq.spliced(&p.kept);
+                        if p.id != id {
+                            rec.set_identifier(&p.id);
+                        }
+                        write_mapping(&id, &p.id, &p.kept, &clipped, &masked);
+                        rec.write(&fasta, &mut out)?;
+                    }
+
+                    if let (Some(a_out), true) = (annotation_out.as_mut(), lifted_ids.insert(key.clone())) {
+                        let kept_ranges: Vec<Range<usize>> = placements.iter().flat_map(|p| p.kept.iter().cloned()).collect();
+                        let kept_features = overlapping(&annotations, &key, &kept_ranges);
+                        for (_, f) in &kept_features {
+                            for l in lift::lift(f, &placements) {
+                                writeln!(a_out, "{}", l)?;
                             }
                         }
-                        None => {
-                            // println!("Got fasta entry with no identifier. Writing unchanged.");
-                            in_seq.write(&fasta, &mut out)?;
+                        // features overlapping no kept range, including any past the end of the sequence
+                        let kept_features: HashSet<usize> = kept_features.into_iter().map(|(i, _)| i).collect();
+                        let mut lost_ranges = clipped.clone();
+                        lost_ranges.push(len..usize::MAX);
+                        for (i, f) in overlapping(&annotations, &key, &lost_ranges) {
+                            if !kept_features.contains(&i) {
+                                eprintln!("Warning: {} `{}' at {}:{}-{} was clipped away",
+                                    f.feature_type, f.attributes.get("ID").unwrap_or("."), f.seq_id, f.start, f.end);
+                            }
                         }
                     }
+                }
+                _ => {
+                    in_seq.write(&fasta, &mut out)?;
+                }
+            }
+        }
+    }
+
+    if let Some(a_out) = annotation_out.as_mut() {
+        for seq_id in annotations.seq_ids().filter(|i| !lifted_ids.contains(*i)) {
+            eprintln!("Warning: annotations on `{}' match no input sequence, and are written unchanged", seq_id);
+            for f in annotations.records(seq_id) {
+                writeln!(a_out, "{}", f)?;
+            }
+        }
+    }
+
+    errors.finish()
+}
+
+/// Quality and adapter trim each read, and any mate, keeping the reads'
+/// identifiers.
+fn trim_reads(matches: &ArgMatches, mut out: Box<dyn Write>, ins: Vec<Box<dyn BufRead>>, mut errors: cli::ErrorHandler) -> io::Result<()> {
+    let arg_or = |name: &str, default: usize| matches.value_of(name).map_or(default, |v| v.parse().unwrap());
+    let mismatches = arg_or("adapterMismatches", 1);
+    let min_overlap = arg_or("adapterMinOverlap", 3);
+    let adapter = |name: &str| matches.value_of(name).map(|a| Adapter::new(a, mismatches, min_overlap));
+    let trimmer = ReadTrimmer {
+        quality: matches.value_of("qualityTrim").map(|q| match QualityTrim::from_str(q).unwrap() {
+            QualityTrim::Window(_) => QualityTrim::Window(arg_or("qualityWindow", 4)),
+            m => m,
+        }),
+        threshold: arg_or("minQuality", 20) as u8,
+        phred_offset: arg_or("phredOffset", 33) as u8,
+        adapter: adapter("adapter"),
+    };
+    let mate_trimmer = ReadTrimmer { adapter: adapter("mateAdapter").or_else(|| trimmer.adapter.clone()), ..trimmer.clone() };
+    let min_length = arg_or("minLength", 0);
+
+    let mut mapping = match matches.value_of("mapping") {
+        Some(m) => Some(fs::File::create(m)?),
+        None => None,
+    };
+    let mut write_mapping = |from: &str, to: &str, len: usize, keep: &Range<usize>| match mapping.as_mut() {
+        Some(f) => {
+            let kept = if keep.is_empty() || to == "." { vec![] } else { vec![keep.clone()] };
+            writeln!(f, "{}\t{}\t{}\t{}\t.", from, to,
+                clip::format_regions(&kept), clip::format_regions(&clip::kept(&kept, len)))
+        }
+        None => Ok(()),
+    };
+
+    let fasta = cli::fasta_format(matches);
+    let validator = cli::validator(matches);
+    let mut mates = match matches.value_of("mateIn") {
+        Some(m) => Some((m, SeqReader::new(BufReader::new(fs::File::open(m)?))?.with_validator(validator))),
+        None => None,
+    };
+    let mut mate_out = match matches.value_of("mateOut") {
+        Some(m) => Some(chunks::write_to_file_or_stdout(Some(m))?),
+        None => None,
+    };
+
+    let names = cli::input_names(matches.values_of("seqIn"));
+    for (name, in_reader) in names.iter().zip(ins) {
+        let mut reader = SeqReader::new(in_reader)?.with_validator(validator);
+        while let Some(in_seq) = reader.next() {
+            errors.warn(name, reader.take_warnings());
+            let in_seq = errors.record(name, in_seq)?;
+            let mate = match mates.as_mut() {
+                Some((mate_name, mate_reader)) => match mate_reader.next() {
+                    Some(m) => {
+                        errors.warn(mate_name, mate_reader.take_warnings());
+                        Some(errors.record(mate_name, m)?)
+                    },
+                    None => return Err(io::Error::new(io::ErrorKind::InvalidData,
+                        format!("Mate file {} has fewer records than {}", mate_name, name))),
                 },
-                Err(e) => println!("Error parsing fasta input:\n{:#?}\n", e)
+                None => None,
+            };
+            // drop both reads of a pair if either can't be parsed
+            let (in_seq, mate) = match (in_seq, mate) {
+                (Some(s), None) => (s, None),
+                (Some(s), Some(Some(m))) => (s, Some(m)),
+                _ => continue,
+            };
+
+            let id = in_seq.description().identifier.unwrap_or_default();
+            let keep = trimmer.trim(in_seq.seq(), in_seq.qualities());
+            let mut long_enough = keep.len() >= min_length;
+            let mate_trim = match &mate {
+                Some(m) => {
+                    let mate_id = m.description().identifier.unwrap_or_default();
+                    if pair_id(&id) != pair_id(&mate_id) {
+                        return Err(io::Error::new(io::ErrorKind::InvalidData,
+                            format!("Mates are out of step: `{}' is paired with `{}'", id, mate_id)));
+                    }
+                    let mate_keep = mate_trimmer.trim(m.seq(), m.qualities());
+                    long_enough &= mate_keep.len() >= min_length;
+                    Some((m, mate_id, mate_keep))
+                }
+                None => None,
+            };
+
+            let to = if long_enough { id.as_str() } else { "." };
+            write_mapping(&id, to, in_seq.seq().len(), &keep)?;
+            if long_enough {
+                in_seq.sub_record(keep.start, keep.end).write(&fasta, &mut out)?;
+            }
+            if let (Some((m, mate_id, mate_keep)), Some(m_out)) = (mate_trim, mate_out.as_mut()) {
+                let to = if long_enough { mate_id.as_str() } else { "." };
+                write_mapping(&mate_id, to, m.seq().len(), &mate_keep)?;
+                if long_enough {
+                    m.sub_record(mate_keep.start, mate_keep.end).write(&fasta, m_out)?;
+                }
             }
         }
     }
 
-    Ok(())
+    if let Some((mate_name, reader)) = mates.as_mut() {
+        if reader.next().is_some() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData,
+                format!("Mate file {} has more records than the reads", mate_name)));
+        }
+    }
+
+    errors.finish()
+}
+
+/// The identifier shared by both reads of a pair, without any `/1` or `/2`
+/// suffix.
+fn pair_id(id: &str) -> &str {
+    id.strip_suffix("/1").or_else(|| id.strip_suffix("/2")).unwrap_or(id)
+}
+
+/// The records on `seq_id` overlapping any of `ranges`, with their positions
+/// in the index, once each and in order of start.
+fn overlapping<'a>(index: &'a GffIndex, seq_id: &str, ranges: &[Range<usize>]) -> Vec<(usize, &'a GffRecord)> {
+    let mut found: Vec<usize> = ranges.iter()
+        .flat_map(|r| index.overlapping_indices(seq_id, r.start as u64..r.end as u64))
+        .collect();
+    found.sort_unstable();
+    found.dedup();
+    found.into_iter()
+        .filter_map(|i| index.record(seq_id, i).map(|f| (i, f)))
+        .collect()
+}
+
+/// Stream the records of a GFF3 file, up to any `##FASTA` section, into an
+/// index, passing any that can't be parsed to `errors`.
+fn read_gff(file_name: &str, errors: &mut cli::ErrorHandler) -> io::Result<GffIndex> {
+    index_records(file_name, GffReader::new(BufReader::new(fs::File::open(file_name)?)), |g| g, errors)
+}
+
+/// Stream the intervals of a BED file into an index of GFF3 records, passing
+/// any that can't be parsed to `errors`.
+fn read_bed(file_name: &str, errors: &mut cli::ErrorHandler) -> io::Result<GffIndex> {
+    index_records(file_name, BedReader::new(BufReader::new(fs::File::open(file_name)?)), |b| GffRecord::from(&b), errors)
+}
+
+/// Index records as they are read, without holding the whole file first.
+/// Reading stops at the first error that `errors` doesn't let through.
+fn index_records<T, I, F>(file_name: &str, records: I, to_gff: F, errors: &mut cli::ErrorHandler) -> io::Result<GffIndex>
+where
+    I: Iterator<Item = Result<T, ReadError>>,
+    F: Fn(T) -> GffRecord,
+{
+    let mut failed = None;
+    let index = GffIndex::new(records
+        .map_while(|r| match r {
+            Ok(r) => Some(Some(to_gff(r))),
+            Err(ReadError::Parse(e)) => match errors.handle(file_name, e) {
+                Ok(()) => Some(None),
+                Err(e) => { failed = Some(e); None }
+            },
+            Err(ReadError::Io(e)) => { failed = Some(e); None }
+        })
+        .flatten());
+    match failed {
+        Some(e) => Err(e),
+        None => Ok(index),
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse(args: &[&str]) -> clap::Result<ArgMatches<'static>> {
+        app().get_matches_from_safe(std::iter::once("tr1m").chain(args.iter().copied()))
+    }
+
+    #[test]
+    fn test_region_args_need_gff_or_bed() {
+        let region_args: [&[&str]; 4] = [
+            &["--internal", "excise"],
+            &["--mask", "soft"],
+            &["--mask", "hard", "--maskTypes", "vector"],
+            &["--annotation", "a.gff", "--annotationOut", "b.gff"],
+        ];
+        for args in &region_args {
+            assert!(parse(&[&["--gff", "r.gff"], *args].concat()).is_ok());
+            assert!(parse(&[&["--bed", "r.bed"], *args].concat()).is_ok());
+            assert_eq!(parse(args).unwrap_err().kind, clap::ErrorKind::MissingRequiredArgument);
+            assert_eq!(parse(&[&["--adapter", "AGATCGGAAGAGC"], *args].concat()).unwrap_err().kind, clap::ErrorKind::ArgumentConflict);
+            assert_eq!(parse(&[&["--minLength", "30"], *args].concat()).unwrap_err().kind, clap::ErrorKind::ArgumentConflict);
+        }
+        assert!(parse(&["--adapter", "AGATCGGAAGAGC"]).is_ok());
+    }
 }
@@ -0,0 +1,171 @@
+//! Quality and adapter trimming of reads.
+
+use std::ops::Range;
+use std::str::FromStr;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum QualityTrim {
+    /// Cut the read at the start of the first window of this many bases with a
+    /// mean quality below the threshold.
+    Window(usize),
+    /// Keep the stretch of the read with the largest sum of quality less the
+    /// threshold, as in the modified Mott algorithm.
+    Mott,
+}
+
+impl FromStr for QualityTrim {
+    type Err = String;
+
+    /// Parses the method name, with the default window size of 4.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "window" => Ok(QualityTrim::Window(4)),
+            "mott" => Ok(QualityTrim::Mott),
+            e => Err(format!("Unknown quality trimming method `{}'. Expected window or mott", e)),
+        }
+    }
+}
+
+/// The bases to keep after sliding-window trimming of Phred `quals`.
+pub fn window_trim(quals: &[u8], window: usize, threshold: u8) -> Range<usize> {
+    let window = window.clamp(1, quals.len().max(1));
+    let threshold = threshold as usize * window;
+    let mut sum: usize = quals.iter().take(window).map(|q| *q as usize).sum();
+    for start in 0..=quals.len().saturating_sub(window) {
+        if start > 0 {
+            sum = sum + quals[start + window - 1] as usize - quals[start - 1] as usize;
+        }
+        if sum < threshold {
+            return 0..start
+        }
+    }
+    0..quals.len()
+}
+
+/// The bases to keep after Mott trimming of Phred `quals`. Empty if every base
+/// is below the threshold.
+pub fn mott_trim(quals: &[u8], threshold: u8) -> Range<usize> {
+    let mut best = 0..0;
+    let mut best_score = 0;
+    let mut start = 0;
+    let mut score = 0;
+    for (i, q) in quals.iter().enumerate() {
+        score += *q as i64 - threshold as i64;
+        if score <= 0 {
+            start = i + 1;
+            score = 0;
+        } else if score > best_score {
+            best_score = score;
+            best = start..i + 1;
+        }
+    }
+    best
+}
+
+/// An adapter to find and remove from the 3' end of reads.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Adapter {
+    pub seq: Vec<u8>,
+    /// Mismatches allowed against the whole adapter. Partial overlaps at the
+    /// end of a read allow a proportional number, rounded down.
+    pub mismatches: usize,
+    /// The fewest adapter bases that must overlap the end of a read.
+    pub min_overlap: usize,
+}
+
+impl Adapter {
+    pub fn new(seq: &str, mismatches: usize, min_overlap: usize) -> Adapter {
+        Adapter { seq: seq.to_ascii_uppercase().into_bytes(), mismatches, min_overlap: min_overlap.max(1) }
+    }
+
+    /// Where the adapter starts in `read`, if it is there.
+    pub fn find(&self, read: &[u8]) -> Option<usize> {
+        (0..read.len()).find(|i| {
+            let overlap = self.seq.len().min(read.len() - i);
+            if overlap < self.min_overlap { return false }
+            let allowed = self.mismatches * overlap / self.seq.len();
+            read[*i..i + overlap].iter().zip(&self.seq)
+                .filter(|(r, a)| !r.eq_ignore_ascii_case(a))
+                .nth(allowed)
+                .is_none()
+        })
+    }
+}
+
+/// Everything that reads are trimmed by.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ReadTrimmer {
+    pub quality: Option<QualityTrim>,
+    pub threshold: u8,
+    pub phred_offset: u8,
+    pub adapter: Option<Adapter>,
+}
+
+impl ReadTrimmer {
+    /// The bases of a read to keep, counting from 0. Quality trimming is
+    /// skipped for reads without `qualities`.
+    pub fn trim(&self, seq: &str, qualities: Option<&str>) -> Range<usize> {
+        let mut keep = match (self.quality, qualities) {
+            (Some(method), Some(q)) => {
+                let quals: Vec<u8> = q.bytes().map(|q| q.saturating_sub(self.phred_offset)).collect();
+                match method {
+                    QualityTrim::Window(w) => window_trim(&quals, w, self.threshold),
+                    QualityTrim::Mott => mott_trim(&quals, self.threshold),
+                }
+            }
+            _ => 0..seq.len(),
+        };
+        if let Some(a) = &self.adapter {
+            if let Some(at) = a.find(&seq.as_bytes()[keep.clone()]) {
+                keep.end = keep.start + at;
+            }
+        }
+        keep
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_window_trim() {
+        let quals = [30, 30, 30, 30, 30, 10, 10, 30, 30];
+        assert_eq!(window_trim(&quals, 2, 20), 0..5);
+        assert_eq!(window_trim(&quals, 4, 21), 0..3);
+        assert_eq!(window_trim(&quals, 4, 20), 0..9);
+        assert_eq!(window_trim(&[10, 10], 4, 20), 0..0);
+        assert_eq!(window_trim(&[], 4, 20), 0..0);
+    }
+
+    #[test]
+    fn test_mott_trim() {
+        assert_eq!(mott_trim(&[2, 2, 30, 30, 15, 30, 30, 2, 40, 2], 20), 2..9);
+        assert_eq!(mott_trim(&[2, 2, 2], 20), 0..0);
+    }
+
+    #[test]
+    fn test_adapter_find() {
+        let adapter = Adapter::new("AGATCGGAAGAGC", 1, 3);
+        assert_eq!(adapter.find(b"ACGTACGTAGATCGGAAGAGCACAC"), Some(8));
+        assert_eq!(adapter.find(b"ACGTACGTAGATCGGTAGAGCACAC"), Some(8));
+        assert_eq!(adapter.find(b"ACGTACGTAGTTCGGTAGAGCACAC"), None);
+        assert_eq!(adapter.find(b"ACGTACGTACGTAGATC"), Some(12));
+        assert_eq!(adapter.find(b"ACGTACGTACGTACAGA"), Some(14));
+        assert_eq!(adapter.find(b"ACGTACGTACGTACGTA"), None);
+    }
+
+    #[test]
+    fn test_read_trimmer() {
+        let trimmer = ReadTrimmer {
+            quality: Some(QualityTrim::Window(2)),
+            threshold: 20,
+            phred_offset: 33,
+            adapter: Some(Adapter::new("AGATCGGAAGAGC", 1, 3)),
+        };
+        assert_eq!(trimmer.trim("ACGTAGATCGG", Some("IIIIIIIIII#")), 0..4);
+        assert_eq!(trimmer.trim("ACGTACGTAGA", Some("IIIII##IIII")), 0..5);
+        assert_eq!(trimmer.trim("ACGTACGTAGA", None), 0..8);
+    }
+}
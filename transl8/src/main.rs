@@ -4,11 +4,13 @@ use std::io::{
     BufRead,
     Write
 };
+use std::ops::Range;
 
 extern crate clap;
 use clap::{
     Arg,
     App,
+    ArgMatches,
     crate_version,
     crate_authors,
 };
@@ -16,10 +18,9 @@ use clap::{
 use bio::{
     dna::*,
     seq::fasta::*,
+    seq::record::SeqReader,
 };
 
-use chunks;
-
 fn main() -> Result<(), io::Error> {
     // todo - toggle for
     //    single output per frame
@@ -33,7 +34,19 @@ fn main() -> Result<(), io::Error> {
     //
     // todo - gzip support on input & output
 
-    let matches = App::new("transl8")
+    let matches = app().get_matches();
+
+    let mut out =
+        chunks::write_to_file_or_stdout(matches.value_of("seqOut"))?;
+
+    let ins: Vec<Box<dyn BufRead>> =
+        chunks::read_from_files_or_stdin(matches.values_of("seqIn"))?;
+
+    run(&matches, ins, &mut out)
+}
+
+fn app<'a, 'b>() -> App<'a, 'b> {
+    App::new("transl8")
         .version(crate_version!())
         .author(crate_authors!())
         .about("Performs 6-frame translation on DNA sequences")
@@ -42,75 +55,167 @@ fn main() -> Result<(), io::Error> {
             .long("seqIn")
             .multiple(true)
             .takes_value(true)
-            .help("Sequence input file. If not provided, defaults to STDIN."))
+            .help("FASTA or FASTQ sequence input file. If not provided, defaults to STDIN."))
         .arg(Arg::with_name("seqOut")
             .short("o")
             .long("seqOut")
             .multiple(false)
             .takes_value(true)
             .help("Sequence output file. If not provided, defaults to STDOUT."))
-        .get_matches();
-
-    let mut out =
-        chunks::write_to_file_or_stdout(matches.value_of("seqOut"))?;
-
-    let ins: Vec<Box<dyn BufRead>> =
-        chunks::read_from_files_or_stdin(matches.values_of("seqIn"))?;
+        .arg(Arg::with_name("maskedAsX")
+            .long("maskedAsX")
+            .multiple(false)
+            .takes_value(false)
+            .help("Translate codons that overlap soft-masked (lower case) bases as X."))
+        .args(&cli::complexity_args())
+        .args(&cli::fasta_format_args())
+        .args(&cli::validation_args())
+        .arg(cli::error_policy_arg())
+}
 
-    let delim = chunks::Delim::new(b">", false);
-    let fasta = FastaFormat::new();
-    for in_reader in ins {
-        for chunk in chunks::chunks(in_reader, &delim) {
-            let chunk = chunk.expect("Failed to read chunk");
-            let chunk_text = std::str::from_utf8(&chunk).unwrap();
-            match parse_fastas(&chunk_text) {
-                Ok((_, in_seqs)) => for (i, in_seq) in in_seqs.iter().enumerate() {
-                    let fd = FastaDescription::read(&in_seq.descr_line);
-                    write_6_phases(&fasta, &fd.identifier.unwrap_or_else(|| i.to_string()), &in_seq.seq, &mut out)?;
-                },
-                Err(e) => println!("Error parsing fasta input:\n{:#?}\n", e)
-            }
+fn run<W : Write>(matches: &ArgMatches, ins: Vec<Box<dyn BufRead>>, out: &mut W) -> Result<(), io::Error> {
+    let fasta = cli::fasta_format(matches);
+    let validator = cli::validator(matches);
+    let low_complexity = cli::low_complexity(matches);
+    // either mask gives the same protein, as only the low-complexity codons become X
+    let lc_mask = cli::low_complexity_mask(matches).is_some();
+    let masked_x = matches.is_present("maskedAsX");
+    let names = cli::input_names(matches.values_of("seqIn"));
+    let mut errors = cli::ErrorHandler::new(cli::ErrorPolicy::from_matches(matches));
+    for (name, in_reader) in names.iter().zip(ins) {
+        let mut reader = SeqReader::new(in_reader)?.with_validator(validator);
+        for i in 0.. {
+            let in_seq = match reader.next() {
+                Some(s) => s,
+                None => break,
+            };
+            errors.warn(name, reader.take_warnings());
+            let in_seq = match errors.record(name, in_seq)? {
+                Some(s) => s,
+                None => continue,
+            };
+            let fd = in_seq.description();
+            let lc_intervals = if lc_mask { low_complexity.intervals(in_seq.seq()) } else { Vec::new() };
+            write_6_phases(&fasta, &fd.identifier.unwrap_or_else(|| i.to_string()), in_seq.seq(), masked_x, &lc_intervals, out)?;
         }
     }
 
-    Ok(())
+    errors.finish()
 }
 
-fn write_6_phases<W : Write>(fasta: &FastaFormat, id: &str, dna_str: &str, out: &mut W) -> Result<(), io::Error> {
-    let lc_dna = &dna_str.to_lowercase()[..];
+/// Replace the amino acids of `protein` whose codon, starting `phase` bases
+/// into the sequence, covers a low-complexity base.
+fn mask_codons(protein: String, low_complexity: &[bool], phase: usize) -> String {
+    if !low_complexity.contains(&true) {
+        return protein;
+    }
+    protein.chars()
+        .enumerate()
+        .map(|(k, aa)| {
+            let codon = phase + 3 * k..phase + 3 * k + 3;
+            if low_complexity.get(codon).is_some_and(|c| c.contains(&true)) { 'X' } else { aa }
+        })
+        .collect()
+}
+
+fn write_6_phases<W : Write>(fasta: &FastaFormat, id: &str, dna_str: &str, masked_x: bool, lc_intervals: &[Range<usize>], out: &mut W) -> Result<(), io::Error> {
+    // keep the case when translating masked codons to X, as it marks the masked bases
+    let lc_dna = &(if masked_x { dna_str.to_string() } else { dna_str.to_lowercase() })[..];
     let rev_cmp = reverse_complement(lc_dna);
+    let translate = |codons: &[&str]| if masked_x { translate_masked(codons) } else { translate(codons) };
+
+    let mut lc_fwd = vec![false; dna_str.len()];
+    for r in lc_intervals {
+        lc_fwd[r.start.min(dna_str.len())..r.end.min(dna_str.len())].fill(true);
+    }
+    let lc_rev: Vec<bool> = lc_fwd.iter().rev().copied().collect();
 
     let phase0 = FastaRecord { 
         descr_line: format!("{}_phase_0", id),
-        seq: translate(&frame(lc_dna, 0))
+        seq: mask_codons(translate(&frame(lc_dna, 0)), &lc_fwd, 0)
     };
     let phase1 = FastaRecord { 
         descr_line: format!("{}_phase_1", id),
-        seq: translate(&frame(lc_dna, 1))
+        seq: mask_codons(translate(&frame(lc_dna, 1)), &lc_fwd, 1)
     };
     let phase2 = FastaRecord { 
         descr_line: format!("{}_phase_2", id),
-        seq: translate(&frame(lc_dna, 2))
+        seq: mask_codons(translate(&frame(lc_dna, 2)), &lc_fwd, 2)
     };
     let phase3 = FastaRecord {
         descr_line: format!("{}_phase_3", id),
-        seq: translate(&frame(&rev_cmp, 3))
+        seq: mask_codons(translate(&frame(&rev_cmp, 3)), &lc_rev, 3)
     };
     let phase4 = FastaRecord {
         descr_line: format!("{}_phase_4", id),
-        seq: translate(&frame(&rev_cmp, 4))
+        seq: mask_codons(translate(&frame(&rev_cmp, 4)), &lc_rev, 4)
     };
     let phase5 = FastaRecord {
         descr_line: format!("{}_phase_5", id),
-        seq: translate(&frame(&rev_cmp, 5))
+        seq: mask_codons(translate(&frame(&rev_cmp, 5)), &lc_rev, 5)
     };
 
-    phase0.write(&fasta, out)?;
-    phase1.write(&fasta, out)?;
-    phase2.write(&fasta, out)?;
-    phase3.write(&fasta, out)?;
-    phase4.write(&fasta, out)?;
-    phase5.write(&fasta, out)?;
+    phase0.write(fasta, out)?;
+    phase1.write(fasta, out)?;
+    phase2.write(fasta, out)?;
+    phase3.write(fasta, out)?;
+    phase4.write(fasta, out)?;
+    phase5.write(fasta, out)?;
 
     Ok(())
-}
\ No newline at end of file
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// The phase 0 translation of `seq` with the given arguments.
+    fn phase_0(args: &[&str], seq: &str) -> String {
+        let matches = app().get_matches_from(std::iter::once("transl8").chain(args.iter().copied()));
+        let input: Box<dyn BufRead> = Box::new(io::Cursor::new(format!(">s\n{}\n", seq).into_bytes()));
+        let mut out = Vec::new();
+        run(&matches, vec![input], &mut out).unwrap();
+        String::from_utf8(out).unwrap().lines()
+            .skip(1)
+            .take_while(|l| !l.starts_with('>'))
+            .collect()
+    }
+
+    #[test]
+    fn test_mask_low_complexity() {
+        // one codon for each amino acid, ACDEFGHIKLMNPQRSTVWY
+        let flank = "GCTTGTGATGAATTTGGTCATATTAAACTTATGAATCCTCAACGTTCTACTGTTTGGTAT".repeat(2);
+        let seq = format!("{}{}{}", flank, "A".repeat(96), flank);
+        let aas = "ACDEFGHIKLMNPQRSTVWY".repeat(2);
+        let unmasked = phase_0(&[], &seq);
+        assert_eq!(unmasked, format!("{}{}{}", aas, "K".repeat(32), aas));
+        for mode in &["soft", "hard"] {
+            let masked = phase_0(&["--maskLowComplexity", mode], &seq);
+            assert!(masked.starts_with("ACDEFGHIKLMNPQRSTVWY"), "{}", masked);
+            assert!(masked.ends_with("ACDEFGHIKLMNPQRSTVWY"), "{}", masked);
+            assert!(masked.contains(&"X".repeat(32)), "{}", masked);
+            assert_eq!(masked.len(), unmasked.len());
+            assert!(masked.chars().zip(unmasked.chars()).all(|(m, u)| m == u || m == 'X'), "{}", masked);
+        }
+    }
+
+    #[test]
+    fn test_mask_low_complexity_keeps_soft_masking() {
+        // soft-masked repeats outside the low-complexity region still translate
+        let flank = "GCTTGTGATGAATTTGGTCATATTAAACTTATGAATCCTCAACGTTCTACTGTTTGGTAT".repeat(2);
+        let seq = format!("{}{}{}", flank.to_lowercase(), "A".repeat(96), flank);
+        let aas = "ACDEFGHIKLMNPQRSTVWY".repeat(2);
+        let masked = phase_0(&["--maskLowComplexity", "soft"], &seq);
+        assert!(masked.starts_with(&aas[..20]), "{}", masked);
+        assert!(masked.contains(&"X".repeat(32)), "{}", masked);
+        assert!(phase_0(&["--maskedAsX"], &seq).starts_with(&"X".repeat(40)));
+    }
+
+    #[test]
+    fn test_ambiguous_codons_are_stops() {
+        assert_eq!(phase_0(&[], "ATGNNNTAA"), "M**");
+        assert_eq!(phase_0(&["--maskedAsX"], "ATGNNNTAA"), "M**");
+        assert_eq!(phase_0(&["--maskedAsX"], "ATGnnnTAA"), "MX*");
+    }
+}